@@ -1,14 +1,13 @@
 use std::sync::atomic::AtomicU32;
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 
-use bevy::core::Time;
 use bevy::ecs::prelude::*;
 use bevy::ecs::system::SystemState;
 use bevy::prelude::{Children, DespawnRecursiveExt};
 
 use crate::dom::Control;
 use crate::observer::{Observer, UninitObserver};
-use crate::runtime::{UiScratchSpace, UpdateFunc};
+use crate::runtime::{UiClock, UiScratchSpace, UpdateFunc};
 
 #[derive(Component)]
 pub(crate) struct ActiveTween {
@@ -77,53 +76,50 @@ where
         self.observer.register_self(world, |mut observer, world| {
             let (obs, arc) = TweenObserver::new();
             let uf = uf(obs, world);
-            let ufm = Arc::new(Mutex::new(None));
-            let ufmc = ufm.clone();
+            let owner = uf.clone();
             let mut first = true;
             let mut current = None;
-            let (uf, marker) = UpdateFunc::new::<(), _>(move |world| {
-                if uf.flagged() {
-                    ufmc.lock().unwrap().take();
-                    return;
-                }
-                let (val, changed) = observer.get(world);
-                if !changed && !first {
-                    return;
-                }
-                let old = if first {
-                    first = false;
-                    val
-                } else {
-                    f32::from_bits(arc.load(std::sync::atomic::Ordering::SeqCst))
-                };
-                arc.store(f32::to_bits(val), std::sync::atomic::Ordering::SeqCst);
-
-                if let Some(ct) = current {
-                    if let Some(mut current) = world.get_mut::<ActiveTween>(ct) {
-                        let intp = current.time_left / current.duration;
-                        current.start =
-                            (current.start - current.end).mul_add(intp.clamp(0., 1.), current.end);
-                        current.end = val;
-                        current.time_left = current.duration;
+            UpdateFunc::new_tied::<TweenObserver, _>(
+                world,
+                move |world| {
+                    let (val, changed) = observer.get(world);
+                    if !changed && !first {
                         return;
                     }
-                }
-                current = Some(
-                    world
-                        .spawn()
-                        .insert(ActiveTween {
-                            duration: self.settings.duration,
-                            time_left: self.settings.duration,
-                            start: old,
-                            end: val,
-                            arc: arc.clone(),
-                            uf: uf.clone(),
-                        })
-                        .id(),
-                );
-            });
-            *ufm.lock().unwrap() = Some(marker);
-            uf
+                    let old = if first {
+                        first = false;
+                        val
+                    } else {
+                        f32::from_bits(arc.load(std::sync::atomic::Ordering::SeqCst))
+                    };
+                    arc.store(f32::to_bits(val), std::sync::atomic::Ordering::SeqCst);
+
+                    if let Some(ct) = current {
+                        if let Some(mut current) = world.get_mut::<ActiveTween>(ct) {
+                            let intp = current.time_left / current.duration;
+                            current.start = (current.start - current.end)
+                                .mul_add(intp.clamp(0., 1.), current.end);
+                            current.end = val;
+                            current.time_left = current.duration;
+                            return;
+                        }
+                    }
+                    current = Some(
+                        world
+                            .spawn()
+                            .insert(ActiveTween {
+                                duration: self.settings.duration,
+                                time_left: self.settings.duration,
+                                start: old,
+                                end: val,
+                                arc: arc.clone(),
+                                uf: uf.clone(),
+                            })
+                            .id(),
+                    );
+                },
+                &owner,
+            )
         })
     }
 }
@@ -138,13 +134,18 @@ impl<'a> Observer<'a> for TweenObserver {
 }
 
 pub(crate) fn tween_system(
-    time: Res<Time>,
+    clock: Res<UiClock>,
     mut tweens: Query<(Entity, &mut ActiveTween)>,
     mut commands: Commands,
     ui: Res<UiScratchSpace>,
 ) {
     for (e, mut tween) in tweens.iter_mut() {
-        tween.time_left -= time.delta_seconds();
+        if tween.uf.flagged() {
+            // The widget it was animating is gone
+            commands.entity(e).despawn();
+            continue;
+        }
+        tween.time_left -= clock.delta;
         let intp = tween.time_left / tween.duration;
         let val = (tween.start - tween.end).mul_add(intp.clamp(0., 1.), tween.end);
         tween
@@ -238,7 +239,7 @@ pub(crate) fn transition_system(
         &ActiveTransition,
     )>,
     mut btc_q: Query<&mut BlockingTransitionCount>,
-    time: Res<Time>,
+    clock: Res<UiClock>,
     mut commands: Commands,
 ) {
     for (entity, mut progress, duration, active) in q.iter_mut() {
@@ -275,7 +276,7 @@ pub(crate) fn transition_system(
             }
         };
 
-        progress.progress += time.delta_seconds() / duration;
+        progress.progress += clock.delta / duration;
         if !(0.0..1.0).contains(&progress.progress) {
             progress.direction = None;
             commands.entity(entity).remove::<ActiveTransition>();
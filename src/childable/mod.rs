@@ -17,10 +17,11 @@ struct CnufMarker;
 
 /// The trait for things that can be used to build a group of children.
 ///
-/// Implemented for three groups:
+/// Implemented for four groups:
 /// - Types implementing`FnOnce(&mut McCtx)`
 /// - The return type of `map_child` called on observers.
 /// - The return type of `each` from [`TrackedVec`](tracked::TrackedVec) lenses.
+/// - The return type of `each` from [`query`](crate::observer::query) observers.
 pub trait Childable<M> {
     /// ### INTERNAL METHOD!
     #[doc(hidden)]
@@ -86,7 +87,7 @@ where
         let mut state = TriggerCallState::new(ctx.world);
         let mut last = None;
         let uf = self.0.register_self(ctx.world, |mut observer, world| {
-            let (uf, marker) = UpdateFunc::new::<CnufMarker, _>(move |world| {
+            let (uf, marker) = UpdateFunc::new::<CnufMarker, _>(world, move |world| {
                 let (ret, changed) = observer.get(world);
 
                 parents.retain(|_, entity| world.entities().contains(*entity));
@@ -136,7 +137,7 @@ where
                 state.apply(world);
             });
 
-            world.entity_mut(main_c_parent).insert(marker);
+            marker.attach(world, main_c_parent);
             uf
         });
         uf.run(ctx.world);
@@ -13,6 +13,7 @@ use bevy::{
 use crossbeam_channel::Receiver;
 
 use crate::{
+    debug::{record_dependency, DependencySource},
     dom::ControlBundle,
     lens::Identity,
     observer::{Observer, UninitObserver},
@@ -149,6 +150,7 @@ where
             .unwrap()
             .item_ufs
             .push(uf.clone());
+        record_dependency(world, DependencySource::TrackedItem(self.entity), &uf);
         uf
     }
 }
@@ -184,6 +186,7 @@ impl UninitObserver for IndexObserver {
             .unwrap()
             .index_ufs
             .push(uf.clone());
+        record_dependency(world, DependencySource::TrackedIndex(self.entity), &uf);
         uf
     }
 }
@@ -228,7 +231,7 @@ where
 
         let mut length = 0usize;
         uo.register_self(world, |_, world| {
-            let (uf, marker) = UpdateFunc::new::<(), _>(move |world| {
+            let (uf, marker) = UpdateFunc::new::<(), _>(world, move |world| {
                 let tracked = world_lens.get_mut(world);
 
                 if Some(tracked.id()) != id {
@@ -341,7 +344,7 @@ where
                 }
             });
 
-            world.entity_mut(c_parent).insert(marker);
+            marker.attach(world, c_parent);
             uf.run(world);
             uf
         });
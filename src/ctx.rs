@@ -1,19 +1,49 @@
-use std::marker::PhantomData;
+use std::{
+    borrow::{Borrow, Cow},
+    marker::PhantomData,
+};
 
 use bevy::ecs::{
     prelude::*,
     system::{SystemParam, SystemState},
 };
+use crossbeam_channel::Sender;
 
 use crate::{
     childable::Childable,
+    debug::DebugLabel,
     dom::ControlBundle,
     insertable::Insertable,
-    lens::ComponentLens,
-    observer::{ComponentExistsObserver, Observer, OptComponentObserver, UninitObserver},
-    runtime::{UfMarker, UiScratchSpace, UpdateFunc},
+    lens::{ComponentLens, LocalState, LocalStateLens},
+    observer::{
+        ComponentExistsObserver, IntoObserver, LayoutObserver, Observer, ObserverExt,
+        OptComponentObserver, Provided, UninitContextObserver, UninitObserver,
+        UninitTryContextObserver,
+    },
+    prelude::ObsReturn,
+    runtime::{Hook, UfMarker, UiScratchSpace, UpdateFunc},
 };
 
+// Keeps the effects of a widget alive for as long as it exists
+#[derive(Component)]
+struct Effects(Vec<UfMarker<Effects>>);
+
+// Queues the unmount hooks of a widget when it's despawned
+#[derive(Component)]
+struct UnmountHooks {
+    tx: Sender<Hook>,
+    hooks: Vec<Hook>,
+}
+
+impl Drop for UnmountHooks {
+    fn drop(&mut self) {
+        for hook in self.hooks.drain(..) {
+            // The receiver is only gone if the whole world is being dropped
+            let _ = self.tx.send(hook);
+        }
+    }
+}
+
 /// Entry point for creating entities. Having a `Ctx` means you control all components on this entity, and
 /// can add children
 pub struct Ctx<'a> {
@@ -60,10 +90,86 @@ impl Ctx<'_> {
         widget(self)
     }
 
+    /// Labels this widget, so it can be told apart in [`Ui4Stats`](crate::debug::Ui4Stats).
+    pub fn debug_label(self, label: impl Into<Cow<'static, str>>) -> Self {
+        self.with(DebugLabel(label.into()))
+    }
+
     pub fn state<P: SystemParam>(&mut self) -> SystemState<P> {
         SystemState::new(self.world)
     }
 
+    /// Runs `effect` every time the observed value changes, for reacting with things other than
+    /// this widget's components: playing a sound, sending an event, writing to a resource...
+    ///
+    /// The effect doesn't run for the initial value, and stops once this widget is despawned.
+    /// As it gets mutable access to the world, the observer has to return an owned value - use
+    /// [`copied`](crate::prelude::ObserverExt::copied), [`cloned`](crate::prelude::ObserverExt::cloned)
+    /// or [`map`](crate::prelude::ObserverExt::map) if it doesn't.
+    pub fn effect<O, T, F>(self, observer: O, mut effect: F) -> Self
+    where
+        O: UninitObserver,
+        for<'a> O::Observer: Observer<'a, Return = T>,
+        F: FnMut(T, &mut World) + Send + Sync + 'static,
+    {
+        let entity = self.current_entity;
+        let uf = observer.register_self(self.world, |mut observer, world| {
+            let mut first = true;
+            let (uf, marker) = UpdateFunc::new::<Effects, _>(world, move |world| {
+                let (val, changed) = observer.get(world);
+                if first {
+                    first = false;
+                    return;
+                }
+                if changed {
+                    effect(val, world);
+                }
+            });
+            marker.set_owner(entity);
+            let mut e = world.entity_mut(entity);
+            if let Some(mut effects) = e.get_mut::<Effects>() {
+                effects.0.push(marker);
+            } else {
+                e.insert(Effects(vec![marker]));
+            }
+            uf
+        });
+        uf.run(self.world);
+        self
+    }
+
+    /// Runs `f` once this widget, along with all of its children, has been built.
+    pub fn on_mount(self, f: impl FnOnce(&mut World) + Send + Sync + 'static) -> Self {
+        let ui = self.world.get_resource::<UiScratchSpace>().unwrap();
+        ui.queue_hook(Box::new(f));
+        self
+    }
+
+    /// Runs `f` once this widget is despawned, which is useful for releasing anything the widget
+    /// acquired. If the despawn is delayed, for example by children playing their out transitions,
+    /// so is the hook.
+    pub fn on_unmount(mut self, f: impl FnOnce(&mut World) + Send + Sync + 'static) -> Self {
+        self.add_unmount_hook(Box::new(f));
+        self
+    }
+
+    fn add_unmount_hook(&mut self, hook: Hook) {
+        let tx = self
+            .world
+            .get_resource::<UiScratchSpace>()
+            .unwrap()
+            .hook_sender();
+        let mut e = self.world.entity_mut(self.current_entity);
+        if let Some(mut hooks) = e.get_mut::<UnmountHooks>() {
+            hooks.hooks.push(hook);
+        } else {
+            e.insert(UnmountHooks {
+                tx,
+                hooks: vec![hook],
+            });
+        }
+    }
+
     pub fn with_modified<T, O, F>(self, initial: T, observer: O, mutator: F) -> Self
     where
         T: Component,
@@ -76,7 +182,7 @@ impl Ctx<'_> {
         let entity = self.current_entity;
         let uf = observer.register_self(self.world, |mut observer, world| {
             let mut first = true;
-            let (uf, marker) = UpdateFunc::new::<T, _>(move |world| {
+            let (uf, marker) = UpdateFunc::new::<T, _>(world, move |world| {
                 world.resource_scope(|world, mut ctx: Mut<UiScratchSpace>| {
                     let t = world.entity_mut(entity).remove::<T>().unwrap();
                     let (val, changed) = observer.get(world);
@@ -93,7 +199,7 @@ impl Ctx<'_> {
                     e.insert(t);
                 })
             });
-            world.entity_mut(entity).insert(marker);
+            marker.attach(world, entity);
             uf
         });
         uf.run(self.world);
@@ -126,6 +232,31 @@ impl Ctx<'_> {
         ComponentLens(self.current_entity, PhantomData)
     }
 
+    /// Creates a piece of state private to this widget, and gets a lens for it. This saves
+    /// declaring a component for things like whether an expander is open. The state is kept on
+    /// the widget's entity, so it's dropped along with the widget. A widget can have any number of
+    /// them, of any types.
+    /// ```
+    /// # use bevy::prelude::*;
+    /// # use ui4::prelude::*;
+    /// fn counter(mut ctx: Ctx) -> Ctx {
+    ///     let count = ctx.local_state(0u32);
+    ///     ctx.child(text(count.map(|count: &u32| count.to_string())))
+    ///         .child(button("+").with(OnClick::new(move |world| *count.get_mut(world) += 1)))
+    /// }
+    /// ```
+    pub fn local_state<T: Send + Sync + 'static>(&mut self, initial: T) -> LocalStateLens<T> {
+        let mut e = self.world.entity_mut(self.current_entity);
+        let index = if let Some(mut states) = e.get_mut::<LocalState<T>>() {
+            states.0.push(initial);
+            states.0.len() - 1
+        } else {
+            e.insert(LocalState(vec![initial]));
+            0
+        };
+        LocalStateLens(self.current_entity, index, PhantomData)
+    }
+
     /// Gets an observer for a component on the entity being built.
     pub fn opt_component<T: Component>(&self) -> OptComponentObserver<T> {
         OptComponentObserver(self.current_entity, PhantomData)
@@ -137,6 +268,61 @@ impl Ctx<'_> {
         ComponentExistsObserver(self.current_entity, PhantomData)
     }
 
+    /// Provides a value to everything inside this widget, which can get it with
+    /// [`Ctx::context`] instead of having it passed down through every widget in between. Given an
+    /// observer, the value is kept up to date.
+    /// ```
+    /// # use bevy::prelude::*;
+    /// # use ui4::prelude::*;
+    /// #[derive(Clone)]
+    /// struct Theme {
+    ///     text: Color,
+    /// }
+    ///
+    /// fn label(ctx: Ctx) -> Ctx {
+    ///     let theme = ctx.context::<Theme>();
+    ///     ctx.with(theme.map(|theme: &Theme| UiColor(theme.text)))
+    /// }
+    ///
+    /// fn root(ctx: Ctx) -> Ctx {
+    ///     ctx.provide(Theme { text: Color::WHITE }).child(label)
+    /// }
+    /// ```
+    pub fn provide<T, O, M>(self, value: O) -> Self
+    where
+        T: Clone + Send + Sync + 'static,
+        O: IntoObserver<T, M>,
+    {
+        self.with(
+            value
+                .into_observer()
+                .map(|value: ObsReturn<'_, _, _, O>| Provided(value.borrow().clone())),
+        )
+    }
+
+    /// Gets an observer for the value of type `T` provided by the closest ancestor of this widget,
+    /// through [`Ctx::provide`]. Panics if there's none.
+    pub fn context<T: Send + Sync + 'static>(&self) -> UninitContextObserver<T> {
+        UninitContextObserver(self.current_entity, PhantomData)
+    }
+
+    /// Like [`Ctx::context`], but returns `None` instead of panicking if no ancestor provides a
+    /// `T`, for widgets which work without one.
+    pub fn try_context<T: Send + Sync + 'static>(&self) -> UninitTryContextObserver<T> {
+        UninitTryContextObserver(self.current_entity, PhantomData)
+    }
+
+    /// Gets an observer for the layout computed for the entity being built. Layout happens after
+    /// the ui is updated, so this fires during the following update, and only if the layout
+    /// actually changed. Widgets can use it to adapt to the space they get, but should take care
+    /// not to change their own size back and forth every frame.
+    pub fn layout(&self) -> LayoutObserver {
+        LayoutObserver {
+            entity: self.current_entity,
+            last: None,
+        }
+    }
+
     #[inline]
     pub fn current_entity(&self) -> Entity {
         self.current_entity
@@ -235,3 +421,98 @@ mod stable_impls {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use bevy::prelude::*;
+
+    use crate::lens::LocalState;
+    use crate::prelude::*;
+    use crate::testing::UiHarness;
+
+    struct Score(i32);
+    struct Other(i32);
+
+    #[test]
+    fn effects_run_on_changes_only() {
+        let seen = Arc::new(Mutex::new(vec![]));
+        let seen_c = seen.clone();
+        let mut harness = UiHarness::new(Vec2::new(800., 600.), move |ctx: Ctx| {
+            ctx.world.insert_resource(Score(0));
+            ctx.world.insert_resource(Other(0));
+            ctx.effect(res::<Score>().map(|s: &Score| s.0), move |score, _| {
+                seen_c.lock().unwrap().push(score);
+            })
+        });
+        assert!(seen.lock().unwrap().is_empty());
+
+        harness.world_mut().insert_resource(Score(1));
+        harness.update();
+        assert_eq!(*seen.lock().unwrap(), [1]);
+
+        harness.world_mut().insert_resource(Other(1));
+        harness.update();
+        harness.update();
+        assert_eq!(*seen.lock().unwrap(), [1]);
+
+        // Nothing runs once the widget is gone
+        let root = harness.root();
+        root.despawn(harness.world_mut());
+        harness.world_mut().insert_resource(Score(2));
+        harness.update();
+        assert_eq!(*seen.lock().unwrap(), [1]);
+    }
+
+    #[test]
+    fn mount_and_unmount_hooks_run_once() {
+        let log = Arc::new(Mutex::new(vec![]));
+        let (mount, unmount) = (log.clone(), log.clone());
+        let mut harness = UiHarness::new(Vec2::new(800., 600.), move |ctx: Ctx| {
+            ctx.on_mount(move |_| mount.lock().unwrap().push("mount"))
+                .on_unmount(move |_| unmount.lock().unwrap().push("unmount"))
+        });
+        assert_eq!(*log.lock().unwrap(), ["mount"]);
+
+        harness.update();
+        assert_eq!(*log.lock().unwrap(), ["mount"]);
+
+        let root = harness.root();
+        root.despawn(harness.world_mut());
+        assert_eq!(*log.lock().unwrap(), ["mount", "unmount"]);
+        harness.update();
+        assert_eq!(*log.lock().unwrap(), ["mount", "unmount"]);
+    }
+
+    #[test]
+    fn local_state_lives_on_the_widget() {
+        let mut widget = None;
+        let mut harness = UiHarness::new(Vec2::new(800., 600.), |ctx: Ctx| {
+            widget = Some(ctx.current_entity());
+            let mut ctx = ctx;
+            let left = ctx.local_state(0);
+            let right = ctx.local_state(10);
+            ctx.child(button("+").with(OnClick::new(move |world| {
+                *right.get_mut(world) += 1;
+            })))
+            .child(text(left.map(|n: &i32| format!("left {}", n))).with(Height(Units::Pixels(30.))))
+            .child(
+                text(right.map(|n: &i32| format!("right {}", n))).with(Height(Units::Pixels(30.))),
+            )
+        });
+        let widget = widget.unwrap();
+        assert_eq!(
+            harness.world().get::<LocalState<i32>>(widget).unwrap().0,
+            vec![0, 10]
+        );
+
+        let button = harness.find_by_text("+").unwrap();
+        harness.click_entity(button);
+        assert!(harness.find_by_text("left 0").is_some());
+        assert!(harness.find_by_text("right 11").is_some());
+
+        harness.world_mut().entity_mut(widget).despawn_recursive();
+        assert!(harness.find_with::<LocalState<i32>>().is_empty());
+    }
+}
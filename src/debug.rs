@@ -0,0 +1,440 @@
+use std::{borrow::Cow, fmt::Write, time::Duration};
+
+use bevy::{asset::HandleId, ecs::prelude::*, transform::components::Parent, utils::HashMap};
+
+use crate::runtime::{UpdateFunc, WeakUpdateFunc};
+
+/// A label identifying a widget, and everything inside it, in [`Ui4Stats`]. Usually added through
+/// [`Ctx::debug_label`](crate::ctx::Ctx::debug_label).
+#[derive(Component, Clone)]
+pub struct DebugLabel(pub Cow<'static, str>);
+
+/// Statistics about the work done by the reactive runtime during the last frame.
+///
+/// This is opt-in: insert it as a resource, and it'll be refilled every frame. Timing every update
+/// func isn't free, so only keep it around while profiling. Flushes made by
+/// [`batch`](crate::batch) in the middle of a frame are counted along with ui4's own.
+#[derive(Default)]
+pub struct Ui4Stats {
+    /// The number of passes made over triggered update funcs, across every flush of the frame
+    pub flush_passes: usize,
+    /// The number of update funcs that ran
+    pub runs: usize,
+    /// The number of triggered update funcs that were skipped, as their widget was already gone
+    pub skipped: usize,
+    /// The time spent running update funcs in total
+    pub total_time: Duration,
+    /// Per-func numbers for everything that ran, slowest first
+    pub funcs: Vec<FuncStats>,
+    /// The update funcs which were still triggering each other when
+    /// [`max_flush_passes`](crate::plugin::Ui4Settings::max_flush_passes) ran out, by name and
+    /// entity. Empty unless that happened.
+    pub stuck: Vec<(&'static str, Option<Entity>)>,
+}
+
+/// The numbers for a single update func in [`Ui4Stats`]
+#[derive(Clone)]
+pub struct FuncStats {
+    id: usize,
+    /// The type name of the component (or marker) the update func maintains
+    pub name: &'static str,
+    /// The entity the update func belongs to
+    pub entity: Option<Entity>,
+    /// The [`DebugLabel`] of the entity, or of its closest labeled ancestor
+    pub label: Option<Cow<'static, str>>,
+    pub runs: usize,
+    pub total_time: Duration,
+    pub max_time: Duration,
+}
+
+#[derive(Default)]
+pub(crate) struct StatsCollector {
+    pub skipped: usize,
+    funcs: HashMap<usize, FuncStats>,
+}
+
+impl StatsCollector {
+    pub fn record(&mut self, uf: &UpdateFunc, time: Duration) {
+        let stats = self.funcs.entry(uf.id()).or_insert_with(|| FuncStats {
+            id: uf.id(),
+            name: uf.name(),
+            entity: uf.entity(),
+            label: None,
+            runs: 0,
+            total_time: Duration::ZERO,
+            max_time: Duration::ZERO,
+        });
+        stats.runs += 1;
+        stats.total_time += time;
+        stats.max_time = stats.max_time.max(time);
+    }
+
+    /// Adds the numbers of a single flush to the ones of the frame so far
+    pub fn finish(self, world: &mut World, flush_passes: usize, stuck: &[UpdateFunc]) {
+        let funcs = self
+            .funcs
+            .into_values()
+            .map(|mut stats| {
+                stats.label = stats.entity.and_then(|entity| find_label(world, entity));
+                stats
+            })
+            .collect::<Vec<_>>();
+
+        let mut stats = world.get_resource_mut::<Ui4Stats>().unwrap();
+        stats.flush_passes += flush_passes;
+        stats.runs += funcs.iter().map(|stats| stats.runs).sum::<usize>();
+        stats.skipped += self.skipped;
+        stats.total_time += funcs.iter().map(|stats| stats.total_time).sum::<Duration>();
+        let index = stats
+            .funcs
+            .iter()
+            .enumerate()
+            .map(|(i, stats)| (stats.id, i))
+            .collect::<HashMap<_, _>>();
+        for func in funcs {
+            match index.get(&func.id) {
+                Some(&i) => {
+                    let existing = &mut stats.funcs[i];
+                    existing.runs += func.runs;
+                    existing.total_time += func.total_time;
+                    existing.max_time = existing.max_time.max(func.max_time);
+                }
+                None => stats.funcs.push(func),
+            }
+        }
+        stats.funcs.sort_by(|a, b| b.total_time.cmp(&a.total_time));
+        stats
+            .stuck
+            .extend(stuck.iter().map(|uf| (uf.name(), uf.entity())));
+    }
+}
+
+/// Clears [`Ui4Stats`] at the start of the frame, so flushes can add to it
+pub(crate) fn reset_stats_system(stats: Option<ResMut<Ui4Stats>>) {
+    if let Some(mut stats) = stats {
+        *stats = Ui4Stats::default();
+    }
+}
+
+fn find_label(world: &World, mut entity: Entity) -> Option<Cow<'static, str>> {
+    loop {
+        let e = world.get_entity(entity)?;
+        if let Some(label) = e.get::<DebugLabel>() {
+            return Some(label.0.clone());
+        }
+        entity = e.get::<Parent>()?.0;
+    }
+}
+
+/// Records which update funcs depend on what, for debugging why a widget updates.
+///
+/// This is opt-in: insert it as a resource, and every dependency registered from then on will
+/// be recorded.
+/// ```no_run
+/// # use bevy::prelude::*;
+/// # use ui4::{debug::DependencyGraph, prelude::*};
+/// # fn root(ctx: Ctx) -> Ctx {ctx}
+/// App::new()
+///     .add_plugins(DefaultPlugins)
+///     .add_plugin(Ui4Plugin)
+///     .init_resource::<DependencyGraph>()
+///     .add_plugin(Ui4Root(root));
+/// ```
+#[derive(Default)]
+pub struct DependencyGraph {
+    // Weak, so recording doesn't keep dead update funcs (and whatever they captured) around until
+    // the next prune
+    edges: Vec<(DependencySource, WeakUpdateFunc)>,
+    prune_at: usize,
+}
+
+/// The thing an update func was registered to be triggered by
+#[derive(Clone)]
+pub(crate) enum DependencySource {
+    /// Another update func, through the marker inserted with its component, or a helper tied to
+    /// the update func it feeds
+    UpdateFunc(WeakUpdateFunc),
+    Resource(&'static str),
+    Event(&'static str),
+    Single(&'static str),
+    Query(&'static str),
+    Window(&'static str),
+    Asset {
+        handle: HandleId,
+        asset: &'static str,
+    },
+    Component {
+        entity: Entity,
+        component: &'static str,
+    },
+    OptComponent {
+        entity: Entity,
+        component: &'static str,
+    },
+    ComponentExists {
+        entity: Entity,
+        component: &'static str,
+    },
+    TrackedItem(Entity),
+    TrackedIndex(Entity),
+    Layout(Entity),
+    /// The entity holding the timer which wakes the update func
+    Timer(Entity),
+    /// A future run by `task` or `try_task`, identified by the slot its output goes in
+    Task {
+        id: usize,
+        output: &'static str,
+    },
+}
+
+impl DependencySource {
+    fn id(&self) -> String {
+        match self {
+            DependencySource::UpdateFunc(uf) => uf_id(uf.id()),
+            DependencySource::Resource(name) => format!("res:{}", name),
+            DependencySource::Event(name) => format!("event:{}", name),
+            DependencySource::Single(name) => format!("single:{}", name),
+            DependencySource::Query(name) => format!("query:{}", name),
+            DependencySource::Window(metric) => format!("window:{}", metric),
+            DependencySource::Asset { handle, asset } => format!("asset:{}:{:?}", asset, handle),
+            DependencySource::Component { entity, component } => {
+                format!("component:{:?}:{}", entity, component)
+            }
+            DependencySource::OptComponent { entity, component } => {
+                format!("opt_component:{:?}:{}", entity, component)
+            }
+            DependencySource::ComponentExists { entity, component } => {
+                format!("has_component:{:?}:{}", entity, component)
+            }
+            DependencySource::TrackedItem(entity) => format!("tracked_item:{:?}", entity),
+            DependencySource::TrackedIndex(entity) => format!("tracked_index:{:?}", entity),
+            DependencySource::Layout(entity) => format!("layout:{:?}", entity),
+            DependencySource::Timer(entity) => format!("timer:{:?}", entity),
+            DependencySource::Task { id, output } => format!("task:{}:{:x}", output, id),
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            DependencySource::UpdateFunc(_) => "update_func",
+            DependencySource::Resource(_) => "resource",
+            DependencySource::Event(_) => "event",
+            DependencySource::Single(_) => "single",
+            DependencySource::Query(_) => "query",
+            DependencySource::Window(_) => "window",
+            DependencySource::Asset { .. } => "asset",
+            DependencySource::Component { .. } => "component",
+            DependencySource::OptComponent { .. } => "opt_component",
+            DependencySource::ComponentExists { .. } => "has_component",
+            DependencySource::TrackedItem(_) => "tracked_item",
+            DependencySource::TrackedIndex(_) => "tracked_index",
+            DependencySource::Layout(_) => "layout",
+            DependencySource::Timer(_) => "timer",
+            DependencySource::Task { .. } => "task",
+        }
+    }
+
+    fn label(&self) -> String {
+        match self {
+            DependencySource::UpdateFunc(uf) => uf
+                .upgrade()
+                .map_or_else(|| "dead update func".to_string(), |uf| format!("{:?}", uf)),
+            DependencySource::Resource(name) => format!("Res<{}>", name),
+            DependencySource::Event(name) => format!("EventReader<{}>", name),
+            DependencySource::Single(name) => format!("Single<{}>", name),
+            DependencySource::Query(name) => format!("Query<{}>", name),
+            DependencySource::Window(metric) => format!("Window::{}", metric),
+            DependencySource::Asset { handle, asset } => format!("Handle<{}> {:?}", asset, handle),
+            DependencySource::Component { entity, component } => {
+                format!("{} on {:?}", component, entity)
+            }
+            DependencySource::OptComponent { entity, component } => {
+                format!("Option<{}> on {:?}", component, entity)
+            }
+            DependencySource::ComponentExists { entity, component } => {
+                format!("Has<{}> on {:?}", component, entity)
+            }
+            DependencySource::TrackedItem(entity) => format!("tracked item {:?}", entity),
+            DependencySource::TrackedIndex(entity) => format!("tracked index {:?}", entity),
+            DependencySource::Layout(entity) => format!("layout of {:?}", entity),
+            DependencySource::Timer(entity) => format!("timer {:?}", entity),
+            DependencySource::Task { output, .. } => format!("Task<{}>", output),
+        }
+    }
+
+    fn entity(&self) -> Option<Entity> {
+        match self {
+            DependencySource::UpdateFunc(uf) => uf.upgrade().and_then(|uf| uf.entity()),
+            DependencySource::Resource(_)
+            | DependencySource::Event(_)
+            | DependencySource::Single(_)
+            | DependencySource::Query(_)
+            | DependencySource::Window(_)
+            | DependencySource::Asset { .. }
+            | DependencySource::Task { .. } => None,
+            DependencySource::Component { entity, .. }
+            | DependencySource::OptComponent { entity, .. }
+            | DependencySource::ComponentExists { entity, .. }
+            | DependencySource::TrackedItem(entity)
+            | DependencySource::TrackedIndex(entity)
+            | DependencySource::Layout(entity)
+            | DependencySource::Timer(entity) => Some(*entity),
+        }
+    }
+
+    fn dead(&self) -> bool {
+        match self {
+            DependencySource::UpdateFunc(uf) => uf.upgrade().map_or(true, |uf| uf.flagged()),
+            _ => false,
+        }
+    }
+}
+
+struct GraphNode {
+    kind: &'static str,
+    label: String,
+    entity: Option<Entity>,
+}
+
+fn uf_id(id: usize) -> String {
+    format!("uf:{:x}", id)
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+pub(crate) fn record_dependency(world: &mut World, source: DependencySource, uf: &UpdateFunc) {
+    if let Some(mut graph) = world.get_resource_mut::<DependencyGraph>() {
+        graph.record(source, uf.downgrade());
+    }
+}
+
+impl DependencyGraph {
+    fn record(&mut self, source: DependencySource, uf: WeakUpdateFunc) {
+        // Dead update funcs are only dropped every once in a while, to keep recording cheap
+        if self.edges.len() >= self.prune_at {
+            self.prune();
+            self.prune_at = (self.edges.len() * 2).max(64);
+        }
+        self.edges.push((source, uf));
+    }
+
+    fn prune(&mut self) {
+        self.edges.retain(|(source, uf)| {
+            !source.dead() && uf.upgrade().map_or(false, |uf| !uf.flagged())
+        });
+    }
+
+    /// Forgets every recorded dependency
+    pub fn clear(&mut self) {
+        self.edges.clear();
+    }
+
+    fn live_graph(&self) -> (Vec<(String, GraphNode)>, Vec<(String, String)>) {
+        let mut nodes = HashMap::default();
+        let mut order = vec![];
+        let mut edges = vec![];
+        let mut add_node = |id: String, node: GraphNode| {
+            if !nodes.contains_key(&id) {
+                order.push(id.clone());
+                nodes.insert(id, node);
+            }
+        };
+        for (source, uf) in &self.edges {
+            let uf = match uf.upgrade() {
+                Some(uf) if !uf.flagged() && !source.dead() => uf,
+                _ => continue,
+            };
+            let from = source.id();
+            let to = uf_id(uf.id());
+            add_node(
+                from.clone(),
+                GraphNode {
+                    kind: source.kind(),
+                    label: source.label(),
+                    entity: source.entity(),
+                },
+            );
+            add_node(
+                to.clone(),
+                GraphNode {
+                    kind: "update_func",
+                    label: format!("{:?}", uf),
+                    entity: uf.entity(),
+                },
+            );
+            edges.push((from, to));
+        }
+        let nodes = order
+            .into_iter()
+            .map(|id| {
+                let node = nodes.remove(&id).unwrap();
+                (id, node)
+            })
+            .collect();
+        (nodes, edges)
+    }
+
+    /// Exports the live part of the graph in the Graphviz DOT format
+    pub fn to_dot(&self) -> String {
+        let (nodes, edges) = self.live_graph();
+        let mut out = String::from("digraph ui4 {\n");
+        for (id, node) in &nodes {
+            let shape = if node.kind == "update_func" {
+                "ellipse"
+            } else {
+                "box"
+            };
+            writeln!(
+                out,
+                "    \"{}\" [label=\"{}\", shape={}];",
+                escape(id),
+                escape(&node.label),
+                shape
+            )
+            .unwrap();
+        }
+        for (from, to) in &edges {
+            writeln!(out, "    \"{}\" -> \"{}\";", escape(from), escape(to)).unwrap();
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// Exports the live part of the graph as JSON, in the form of
+    /// `{"nodes": [{"id", "kind", "label", "entity"}], "edges": [{"from", "to"}]}`
+    pub fn to_json(&self) -> String {
+        let (nodes, edges) = self.live_graph();
+        let nodes = nodes
+            .iter()
+            .map(|(id, node)| {
+                let entity = node
+                    .entity
+                    .map_or("null".to_string(), |e| format!("\"{:?}\"", e));
+                format!(
+                    "{{\"id\":\"{}\",\"kind\":\"{}\",\"label\":\"{}\",\"entity\":{}}}",
+                    escape(id),
+                    node.kind,
+                    escape(&node.label),
+                    entity
+                )
+            })
+            .collect::<Vec<_>>();
+        let edges = edges
+            .iter()
+            .map(|(from, to)| {
+                format!(
+                    "{{\"from\":\"{}\",\"to\":\"{}\"}}",
+                    escape(from),
+                    escape(to)
+                )
+            })
+            .collect::<Vec<_>>();
+        format!(
+            "{{\"nodes\":[{}],\"edges\":[{}]}}",
+            nodes.join(","),
+            edges.join(",")
+        )
+    }
+}
@@ -18,13 +18,20 @@ type ShapeQ<'w, 's> = Query<
     'w,
     's,
     (
-        Option<&'static Node>,
         Option<&'static TextBoxCursor>,
         Option<&'static TextDetails>,
         Option<&'static TextAlign>,
         Option<&'static UiColor>,
-        Option<&'static HideOverflow>,
         Option<&'static UiImage>,
+    ),
+>;
+
+type ClipQ<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Option<&'static Node>,
+        Option<&'static HideOverflow>,
         Option<&'static Children>,
     ),
 >;
@@ -40,81 +47,107 @@ fn y_inv(pos: Vec2, window_height: f32) -> Vec2 {
 pub const DEFAULT_FONT: HandleUntyped =
     HandleUntyped::weak_from_u64(Font::TYPE_UUID, 9182127759878421895);
 
-fn push_shapes(
-    vec: &mut Vec<ExtractedUiNode>,
+/// Walks the tree below `entity` in draw order, updating each node's [`ClippedNode`] and calling
+/// `visit` with the node, the clip rect inherited from its ancestors and its z layer.
+fn walk_clips(
     entity: Entity,
     clip: Rect,
-    q: &ShapeQ,
+    q: &ClipQ,
     cn_query: &mut Query<&mut ClippedNode>,
-    text_pipeline: &DefaultTextPipeline,
-    images: &Assets<Image>,
-    atlases: &Assets<TextureAtlas>,
     mut z: u32,
-    window_height: f32,
-    scale_factor: f32,
+    visit: &mut impl FnMut(Entity, &Node, Rect, u32),
 ) {
-    let (node, tb, text_details, text_align, color, hide_overflow, image, children) =
-        q.get(entity).unwrap();
+    let (node, hide_overflow, children) = q.get(entity).unwrap();
 
     let clip = if let Some(node) = node {
         let mut clipped = cn_query.get_mut(entity).unwrap();
         clipped.z_layer = z;
-        let pos = node.pos;
-        let color = color.map(|x| x.0);
         let this_rect = Rect {
-            min: pos,
-            max: pos + node.size,
+            min: node.pos,
+            max: node.pos + node.size,
         };
         clipped.min = clip.min.max(this_rect.min);
         clipped.max = clip.max.min(this_rect.max);
-        if let Some(layout_info) = text_pipeline.get_glyphs(&entity) {
-            let alignment_offset = node.size / -2.0;
-            let text_details = text_details.map(|x| &*x.0).unwrap_or(&[]);
+        visit(entity, node, clip, z);
+        z += 1;
+        if hide_overflow.is_some() {
+            this_rect
+        } else {
+            clip
+        }
+    } else {
+        clip
+    };
 
-            let mut details = text_details
-                .iter()
-                .map(|(style, ends_at)| (style.color, *ends_at))
-                .chain(std::iter::once((color.unwrap_or(Color::WHITE), usize::MAX)));
+    for &child in children.map(|x| &**x).unwrap_or(&[]) {
+        walk_clips(child, clip, q, cn_query, z, visit);
+    }
+}
 
-            let (mut cur_color, mut ends_at) = details.next().unwrap();
-            for text_glyph in &layout_info.glyphs {
-                if text_glyph.byte_index >= ends_at {
-                    let (color, end) = details.next().unwrap();
-                    cur_color = color;
-                    ends_at = end;
-                }
+fn push_shapes(
+    vec: &mut Vec<ExtractedUiNode>,
+    entity: Entity,
+    node: &Node,
+    clip: Rect,
+    z: u32,
+    q: &ShapeQ,
+    text_pipeline: &DefaultTextPipeline,
+    images: &Assets<Image>,
+    atlases: &Assets<TextureAtlas>,
+    window_height: f32,
+    scale_factor: f32,
+) {
+    let (tb, text_details, text_align, color, image) = q.get(entity).unwrap();
+    let pos = node.pos;
+    let color = color.map(|x| x.0);
+    if let Some(layout_info) = text_pipeline.get_glyphs(&entity) {
+        let alignment_offset = node.size / -2.0;
+        let text_details = text_details.map(|x| &*x.0).unwrap_or(&[]);
 
-                let atlas = atlases
-                    .get(text_glyph.atlas_info.texture_atlas.clone_weak())
-                    .unwrap();
+        let mut details = text_details
+            .iter()
+            .map(|(style, ends_at)| (style.color, *ends_at))
+            .chain(std::iter::once((color.unwrap_or(Color::WHITE), usize::MAX)));
 
-                let texture = atlas.texture.clone_weak();
-                let index = text_glyph.atlas_info.glyph_index as usize;
-                let rect = atlas.textures[index];
-                let atlas_size = Some(atlas.size);
-                let text_align = text_align.map(|ta| ta.0).unwrap_or_default();
-                let major_align_offset = Vec2::new(
-                    match text_align.horizontal {
-                        bevy::text::HorizontalAlign::Left => 0.,
-                        bevy::text::HorizontalAlign::Center => {
-                            (node.size.x * scale_factor - layout_info.size.width) / 2.
-                        }
-                        bevy::text::HorizontalAlign::Right => {
-                            node.size.x * scale_factor - layout_info.size.width
-                        }
-                    },
-                    match text_align.vertical {
-                        bevy::text::VerticalAlign::Top => node.size.y - layout_info.size.height,
-                        bevy::text::VerticalAlign::Center => {
-                            (node.size.y * scale_factor - layout_info.size.height) / 2.
-                        }
-                        bevy::text::VerticalAlign::Bottom => 0.,
-                    },
-                );
+        let (mut cur_color, mut ends_at) = details.next().unwrap();
+        for text_glyph in &layout_info.glyphs {
+            if text_glyph.byte_index >= ends_at {
+                let (color, end) = details.next().unwrap();
+                cur_color = color;
+                ends_at = end;
+            }
+
+            let atlas = atlases
+                .get(text_glyph.atlas_info.texture_atlas.clone_weak())
+                .unwrap();
+
+            let texture = atlas.texture.clone_weak();
+            let index = text_glyph.atlas_info.glyph_index as usize;
+            let rect = atlas.textures[index];
+            let atlas_size = Some(atlas.size);
+            let text_align = text_align.map(|ta| ta.0).unwrap_or_default();
+            let major_align_offset = Vec2::new(
+                match text_align.horizontal {
+                    bevy::text::HorizontalAlign::Left => 0.,
+                    bevy::text::HorizontalAlign::Center => {
+                        (node.size.x * scale_factor - layout_info.size.width) / 2.
+                    }
+                    bevy::text::HorizontalAlign::Right => {
+                        node.size.x * scale_factor - layout_info.size.width
+                    }
+                },
+                match text_align.vertical {
+                    bevy::text::VerticalAlign::Top => node.size.y - layout_info.size.height,
+                    bevy::text::VerticalAlign::Center => {
+                        (node.size.y * scale_factor - layout_info.size.height) / 2.
+                    }
+                    bevy::text::VerticalAlign::Bottom => 0.,
+                },
+            );
 
-                let transform = Mat4::from_translation(
-                    y_inv(pos + node.size / 2., window_height).extend(map_z(z)),
-                ) * Mat4::from_scale(Vec3::ONE / scale_factor)
+            let transform =
+                Mat4::from_translation(y_inv(pos + node.size / 2., window_height).extend(map_z(z)))
+                    * Mat4::from_scale(Vec3::ONE / scale_factor)
                     * Mat4::from_translation(
                         (alignment_offset * scale_factor
                             + text_glyph.position
@@ -122,59 +155,12 @@ fn push_shapes(
                             .extend(0.),
                     );
 
-                vec.push(ExtractedUiNode {
-                    transform,
-                    color: cur_color,
-                    rect,
-                    image: texture,
-                    atlas_size,
-                    clip: Some(Rect {
-                        min: y_inv(Vec2::new(clip.min.x, clip.max.y), window_height),
-                        max: y_inv(Vec2::new(clip.max.x, clip.min.y), window_height),
-                    }),
-                });
-            }
-
-            let shape = tb
-                .and_then(|tb| tb.0)
-                .and_then(|cursor| layout_info.glyphs.iter().find(|g| g.byte_index == cursor))
-                .map(|glyph| {
-                    let glyph_pos = glyph.position;
-
-                    ExtractedUiNode {
-                        transform: Mat4::from_translation(
-                            y_inv(pos + glyph_pos, window_height).extend(map_z(z) + 0.1),
-                        ),
-                        color: Color::WHITE,
-                        rect: Rect {
-                            min: Vec2::ZERO,
-                            max: Vec2::new(2., 14.),
-                        },
-                        image: bevy::render::texture::DEFAULT_IMAGE_HANDLE.typed(),
-                        atlas_size: None,
-                        clip: Some(Rect {
-                            min: y_inv(Vec2::new(clip.min.x, clip.max.y), window_height),
-                            max: y_inv(Vec2::new(clip.max.x, clip.min.y), window_height),
-                        }),
-                    }
-                });
-            vec.extend(shape);
-        } else if image
-            .map(|img| images.contains(img.0.clone_weak()))
-            .unwrap_or(true)
-        {
-            let pos = y_inv(pos + node.size / 2., window_height);
             vec.push(ExtractedUiNode {
-                transform: Mat4::from_translation(pos.extend(map_z(z))),
-                color: color.unwrap_or(Color::NONE),
-                rect: Rect {
-                    min: Vec2::ZERO,
-                    max: node.size,
-                },
-                image: image
-                    .map(|i| i.0.clone_weak())
-                    .unwrap_or_else(|| bevy::render::texture::DEFAULT_IMAGE_HANDLE.typed()),
-                atlas_size: None,
+                transform,
+                color: cur_color,
+                rect,
+                image: texture,
+                atlas_size,
                 clip: Some(Rect {
                     min: y_inv(Vec2::new(clip.min.x, clip.max.y), window_height),
                     max: y_inv(Vec2::new(clip.max.x, clip.min.y), window_height),
@@ -182,29 +168,78 @@ fn push_shapes(
             });
         }
 
-        z += 1;
-        if hide_overflow.is_some() {
-            this_rect
-        } else {
-            clip
-        }
+        let shape = tb
+            .and_then(|tb| tb.0)
+            .and_then(|cursor| layout_info.glyphs.iter().find(|g| g.byte_index == cursor))
+            .map(|glyph| {
+                let glyph_pos = glyph.position;
+
+                ExtractedUiNode {
+                    transform: Mat4::from_translation(
+                        y_inv(pos + glyph_pos, window_height).extend(map_z(z) + 0.1),
+                    ),
+                    color: Color::WHITE,
+                    rect: Rect {
+                        min: Vec2::ZERO,
+                        max: Vec2::new(2., 14.),
+                    },
+                    image: bevy::render::texture::DEFAULT_IMAGE_HANDLE.typed(),
+                    atlas_size: None,
+                    clip: Some(Rect {
+                        min: y_inv(Vec2::new(clip.min.x, clip.max.y), window_height),
+                        max: y_inv(Vec2::new(clip.max.x, clip.min.y), window_height),
+                    }),
+                }
+            });
+        vec.extend(shape);
+    } else if image
+        .map(|img| images.contains(img.0.clone_weak()))
+        .unwrap_or(true)
+    {
+        let pos = y_inv(pos + node.size / 2., window_height);
+        vec.push(ExtractedUiNode {
+            transform: Mat4::from_translation(pos.extend(map_z(z))),
+            color: color.unwrap_or(Color::NONE),
+            rect: Rect {
+                min: Vec2::ZERO,
+                max: node.size,
+            },
+            image: image
+                .map(|i| i.0.clone_weak())
+                .unwrap_or_else(|| bevy::render::texture::DEFAULT_IMAGE_HANDLE.typed()),
+            atlas_size: None,
+            clip: Some(Rect {
+                min: y_inv(Vec2::new(clip.min.x, clip.max.y), window_height),
+                max: y_inv(Vec2::new(clip.max.x, clip.min.y), window_height),
+            }),
+        });
+    }
+}
+
+/// Computes [`ClippedNode`]s like [`create_shapes_system`] does, but without touching anything
+/// rendering related. Used when running the ui headlessly.
+pub(crate) fn clip_system(
+    roots: Query<Entity, (With<Node>, Without<Parent>)>,
+    clip_q: ClipQ,
+    mut cn_query: Query<&mut ClippedNode>,
+    windows: Res<Windows>,
+) {
+    let window = if let Some(w) = windows.get_primary() {
+        w
     } else {
-        clip
+        return;
     };
-
-    for &child in children.map(|x| &**x).unwrap_or(&[]) {
-        push_shapes(
-            vec,
-            child,
-            clip,
-            q,
-            cn_query,
-            text_pipeline,
-            images,
-            atlases,
-            z,
-            window_height,
-            scale_factor,
+    for root in roots.iter() {
+        walk_clips(
+            root,
+            Rect {
+                min: Vec2::ZERO,
+                max: Vec2::new(window.width(), window.height()),
+            },
+            &clip_q,
+            &mut cn_query,
+            0,
+            &mut |_, _, _, _| {},
         );
     }
 }
@@ -214,6 +249,7 @@ pub(crate) struct PreExtractedUiNodes(Vec<ExtractedUiNode>);
 
 pub(crate) fn create_shapes_system(
     roots: Query<Entity, (With<Node>, Without<Parent>)>,
+    clip_q: ClipQ,
     shapes_q: ShapeQ,
     mut cn_query: Query<&mut ClippedNode>,
     windows: Res<Windows>,
@@ -229,22 +265,32 @@ pub(crate) fn create_shapes_system(
     };
     let window_width = window.width();
     let window_height = window.height();
+    let scale_factor = window.scale_factor() as f32;
     for root in roots.iter() {
-        push_shapes(
-            &mut shapes.0,
+        walk_clips(
             root,
             Rect {
                 min: Vec2::ZERO,
                 max: Vec2::new(window_width, window_height),
             },
-            &shapes_q,
+            &clip_q,
             &mut cn_query,
-            &text_pipeline,
-            &images,
-            &atlases,
             0,
-            window_height,
-            window.scale_factor() as f32,
+            &mut |entity, node, clip, z| {
+                push_shapes(
+                    &mut shapes.0,
+                    entity,
+                    node,
+                    clip,
+                    z,
+                    &shapes_q,
+                    &text_pipeline,
+                    &images,
+                    &atlases,
+                    window_height,
+                    scale_factor,
+                )
+            },
         );
     }
 }
@@ -36,7 +36,7 @@ where
         let entity = ctx.current_entity;
         let uf = self.register_self(ctx.world, |mut observer, world| {
             let mut first = true;
-            let (uf, marker) = UpdateFunc::new::<T, _>(move |world| {
+            let (uf, marker) = UpdateFunc::new::<T, _>(world, move |world| {
                 let (val, changed) = observer.get(world);
                 if !changed && !first {
                     return;
@@ -48,7 +48,7 @@ where
                     e.insert(val);
                 })
             });
-            world.entity_mut(entity).insert(marker);
+            marker.attach(world, entity);
             uf
         });
         uf.run(ctx.world);
@@ -64,7 +64,7 @@ where
         let entity = ctx.current_entity;
         let uf = self.register_self(ctx.world, |mut observer, world| {
             let mut first = true;
-            let (uf, marker) = UpdateFunc::new::<T, _>(move |world| {
+            let (uf, marker) = UpdateFunc::new::<T, _>(world, move |world| {
                 let (val, changed) = observer.get(world);
                 if !changed && !first {
                     return;
@@ -80,7 +80,7 @@ where
                     }
                 })
             });
-            world.entity_mut(entity).insert(marker);
+            marker.attach(world, entity);
             uf
         });
         uf.run(ctx.world);
@@ -157,3 +157,72 @@ impl<T: Component> WorldLens for ComponentLens<T> {
         )
     }
 }
+
+/// The storage behind [`Ctx::local_state`](crate::ctx::Ctx::local_state), holding every piece
+/// of local state of type `T` on an entity, in the order they were created. Public only because
+/// [`LocalStateLens`] observes it.
+#[doc(hidden)]
+#[derive(Component)]
+pub struct LocalState<T>(pub(crate) Vec<T>);
+
+pub struct LocalStateInner<T>(usize, PhantomData<fn(T) -> T>);
+impl<T> Copy for LocalStateInner<T> {}
+impl<T> Clone for LocalStateInner<T> {
+    fn clone(&self) -> Self {
+        Self(self.0, PhantomData)
+    }
+}
+
+impl<T: Send + Sync + 'static> Lens for LocalStateInner<T> {
+    type In = LocalState<T>;
+    type Out = T;
+
+    fn get<'a>(&self, val: &'a Self::In) -> &'a Self::Out {
+        &val.0[self.0]
+    }
+
+    fn get_mut<'a>(&self, val: &'a mut Self::In) -> &'a mut Self::Out {
+        &mut val.0[self.0]
+    }
+}
+
+pub struct LocalStateLens<T>(
+    pub(crate) Entity,
+    pub(crate) usize,
+    pub(crate) PhantomData<T>,
+);
+impl<T> Copy for LocalStateLens<T> {}
+impl<T> Clone for LocalStateLens<T> {
+    fn clone(&self) -> Self {
+        Self(self.0, self.1, PhantomData)
+    }
+}
+
+impl<T: Send + Sync + 'static> WorldLens for LocalStateLens<T> {
+    type UninitObserver = crate::observer::ComponentObserver<LocalState<T>>;
+    type Observer = crate::observer::ComponentObserver<LocalState<T>>;
+    type Lens = LocalStateInner<T>;
+    type Out = T;
+
+    fn get<'a>(&mut self, world: &'a World) -> &'a Self::Out {
+        &world.get::<LocalState<T>>(self.0).unwrap().0[self.1]
+    }
+
+    fn get_mut<'a>(&self, world: &'a mut World) -> &'a mut Self::Out {
+        &mut world
+            .get_mut::<LocalState<T>>(self.0)
+            .unwrap()
+            .into_inner()
+            .0[self.1]
+    }
+
+    fn to_observer(self) -> (Self::UninitObserver, Self::Lens) {
+        (
+            crate::observer::ComponentObserver {
+                entity: self.0,
+                _marker: PhantomData,
+            },
+            LocalStateInner(self.1, PhantomData),
+        )
+    }
+}
@@ -10,10 +10,14 @@
 pub mod animation;
 /// The core api that makes ui4 tick
 pub mod ctx;
+/// Tools for inspecting the reactive runtime
+pub mod debug;
 /// Types that make up the DOM
 pub mod dom;
 /// The bevy integration
 pub mod plugin;
+/// Utilities for testing widgets headlessly
+pub mod testing;
 /// The built-in widget library
 pub mod widgets;
 
@@ -25,6 +29,9 @@ pub mod observer;
 mod input;
 mod runtime;
 
+pub use observer::combine;
+pub use runtime::batch;
+
 /// A comprehensive getting started guide for ui4
 pub mod tutorial;
 
@@ -40,8 +47,12 @@ pub mod prelude {
     pub use dom::layout::{layout_components::*, Units};
     pub use dom::{Focused, HideOverflow, TextAlign, TextDetails, TextSize};
     pub use lens::WorldLens;
-    pub use observer::{component, res, single, FlattenReturn, IntoObserver, ObserverExt};
-    pub use plugin::{Ui4Plugin, Ui4Root};
+    pub use observer::{
+        asset, combine, component, event, interval, opt_single, query, res, scale_factor, single,
+        task, try_task, window_focused, window_size, FlattenReturn, IntoObserver, NodeLayout,
+        ObserverExt, TaskStatus,
+    };
+    pub use plugin::{Ui4Plugin, Ui4Root, Ui4Settings};
     pub use widgets::button::{OnClick, OnHover, OnRelease, OnUnhover};
     pub type ObsReturn<'a, T, M, O> =
         <<O as IntoObserver<T, M>>::ReturnSpec as observer::ReturnSpec<'a, T>>::R;
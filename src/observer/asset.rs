@@ -0,0 +1,240 @@
+use std::marker::PhantomData;
+
+use bevy::asset::{Asset, AssetEvent, AssetServer, Assets, Handle, HandleId, LoadState};
+use bevy::ecs::prelude::*;
+use bevy::utils::{HashMap, HashSet};
+
+use crate::debug::{record_dependency, DependencySource};
+use crate::runtime::{UiManagedSystems, UiScratchSpace, UpdateFunc};
+
+use super::{Observer, UninitObserver};
+
+struct AssetEntry {
+    state: LoadState,
+    // Bumped whenever the asset is created, modified or removed, or its load state changes
+    version: usize,
+    list: Vec<UpdateFunc>,
+}
+
+struct AssetUpdateFuncs<T>(HashMap<HandleId, AssetEntry>, PhantomData<T>);
+
+pub struct AssetObserver<T: Asset> {
+    handle: Handle<T>,
+    version: Option<usize>,
+}
+
+impl<T: Asset> Clone for AssetObserver<T> {
+    fn clone(&self) -> Self {
+        Self {
+            handle: self.handle.clone(),
+            version: self.version,
+        }
+    }
+}
+
+/// Gets an observer for an asset, returning it if it's loaded along with its load state. It fires
+/// when the asset is created, modified (including hot reloading) or removed, and when its load
+/// state changes, so it can be used to show loading indicators.
+/// ```
+/// # use bevy::prelude::*;
+/// # use bevy::asset::LoadState;
+/// # use ui4::prelude::*;
+/// fn icon_status(ctx: Ctx, icon: Handle<Image>) -> Ctx {
+///     ctx.child(text(asset(icon).map(
+///         |(_, state): (Option<&Image>, LoadState)| match state {
+///             LoadState::Loaded => String::new(),
+///             LoadState::Failed => "Failed to load icon".to_string(),
+///             _ => "Loading...".to_string(),
+///         },
+///     )))
+/// }
+/// ```
+pub fn asset<T: Asset>(handle: Handle<T>) -> AssetObserver<T> {
+    AssetObserver {
+        handle,
+        version: None,
+    }
+}
+
+fn load_state<T: Asset>(
+    id: HandleId,
+    assets: &Assets<T>,
+    server: Option<&AssetServer>,
+) -> LoadState {
+    if assets.contains(id) {
+        LoadState::Loaded
+    } else {
+        server.map_or(LoadState::NotLoaded, |server| server.get_load_state(id))
+    }
+}
+
+impl<'a, T: Asset> Observer<'a> for AssetObserver<T> {
+    type Return = (Option<&'a T>, LoadState);
+
+    fn get(&'a mut self, world: &'a World) -> (Self::Return, bool) {
+        let registry = world.get_resource::<AssetUpdateFuncs<T>>().unwrap();
+        let entry = &registry.0[&self.handle.id];
+        let changed = self.version != Some(entry.version);
+        self.version = Some(entry.version);
+        let asset = world.get_resource::<Assets<T>>().unwrap().get(&self.handle);
+        ((asset, entry.state), changed)
+    }
+}
+
+impl<T: Asset> UninitObserver for AssetObserver<T> {
+    type Observer = Self;
+
+    fn register_self<F: FnOnce(Self::Observer, &mut World) -> UpdateFunc>(
+        self,
+        world: &mut World,
+        uf: F,
+    ) -> UpdateFunc {
+        let id = self.handle.id;
+        let state = load_state(
+            id,
+            world.get_resource::<Assets<T>>().unwrap(),
+            world.get_resource::<AssetServer>(),
+        );
+        world.resource_scope(|world, mut systems: Mut<UiManagedSystems>| {
+            if world.get_resource::<AssetUpdateFuncs<T>>().is_none() {
+                systems.add::<AssetUpdateFuncs<T>, _>(world, asset_track_system::<T>);
+                world.insert_resource(AssetUpdateFuncs::<T>(HashMap::default(), PhantomData));
+            }
+        });
+        world
+            .get_resource_mut::<AssetUpdateFuncs<T>>()
+            .unwrap()
+            .0
+            .entry(id)
+            .or_insert(AssetEntry {
+                state,
+                version: 0,
+                list: vec![],
+            });
+
+        let uf = uf(self, world);
+        world
+            .get_resource_mut::<AssetUpdateFuncs<T>>()
+            .unwrap()
+            .0
+            .get_mut(&id)
+            .unwrap()
+            .list
+            .push(uf.clone());
+        record_dependency(
+            world,
+            DependencySource::Asset {
+                handle: id,
+                asset: std::any::type_name::<T>(),
+            },
+            &uf,
+        );
+        uf
+    }
+}
+
+fn asset_track_system<T: Asset>(
+    mut seen: Local<usize>,
+    ui: Res<UiScratchSpace>,
+    mut registry: ResMut<AssetUpdateFuncs<T>>,
+    mut events: EventReader<AssetEvent<T>>,
+    assets: Res<Assets<T>>,
+    server: Option<Res<AssetServer>>,
+) -> bool {
+    let touched = events
+        .iter()
+        .map(|event| match event {
+            AssetEvent::Created { handle }
+            | AssetEvent::Modified { handle }
+            | AssetEvent::Removed { handle } => handle.id,
+        })
+        .collect::<HashSet<_>>();
+    let prune = ui.any_died_since(&mut seen);
+
+    registry.0.retain(|&id, entry| {
+        let state = load_state(id, &assets, server.as_deref());
+        if touched.contains(&id) || state != entry.state {
+            entry.state = state;
+            entry.version += 1;
+            ui.process_list(&mut entry.list);
+        } else if prune {
+            entry.list.retain(|uf| !uf.flagged());
+        }
+        !entry.list.is_empty()
+    });
+    !registry.0.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use bevy::asset::{AssetServer, FileAssetIo, HandleId, LoadState};
+    use bevy::prelude::*;
+    use bevy::reflect::TypeUuid;
+    use bevy::tasks::TaskPool;
+
+    use crate::prelude::*;
+    use crate::testing::UiHarness;
+
+    #[derive(TypeUuid)]
+    #[uuid = "694ec810-bf37-4368-867d-2f9ca40ae327"]
+    struct Icon(u32);
+
+    #[test]
+    fn fires_on_load_and_asset_events() {
+        let server = AssetServer::new(FileAssetIo::new("assets"), TaskPool::new());
+        let handle = Handle::<Icon>::weak(HandleId::random::<Icon>());
+        let runs = Arc::new(AtomicUsize::new(0));
+        let runs_c = runs.clone();
+        let observed = handle.clone_weak();
+        let mut harness = UiHarness::new(Vec2::new(800., 600.), move |ctx: Ctx| {
+            ctx.world
+                .insert_resource(server.register_asset_type::<Icon>());
+            ctx.world
+                .insert_resource(Events::<AssetEvent<Icon>>::default());
+            ctx.child(text(asset(observed).map(
+                move |(icon, state): (Option<&Icon>, LoadState)| {
+                    runs_c.fetch_add(1, Ordering::Relaxed);
+                    match (icon, state) {
+                        (Some(icon), _) => format!("icon {}", icon.0),
+                        (None, LoadState::Failed) => "failed".to_string(),
+                        (None, _) => "loading".to_string(),
+                    }
+                },
+            )))
+        });
+        assert!(harness.find_by_text("loading").is_some());
+        runs.store(0, Ordering::Relaxed);
+
+        harness
+            .world_mut()
+            .get_resource_mut::<Assets<Icon>>()
+            .unwrap()
+            .set_untracked(handle.id, Icon(1));
+        harness.update();
+        assert_eq!(runs.load(Ordering::Relaxed), 1);
+        assert!(harness.find_by_text("icon 1").is_some());
+
+        harness.update();
+        assert_eq!(runs.load(Ordering::Relaxed), 1);
+
+        let world = harness.world_mut();
+        world
+            .get_resource_mut::<Assets<Icon>>()
+            .unwrap()
+            .get_mut(&handle)
+            .unwrap()
+            .0 = 2;
+        world
+            .get_resource_mut::<Events<AssetEvent<Icon>>>()
+            .unwrap()
+            .send(AssetEvent::Modified {
+                handle: handle.clone_weak(),
+            });
+        harness.update();
+        assert_eq!(runs.load(Ordering::Relaxed), 2);
+        assert!(harness.find_by_text("icon 2").is_some());
+    }
+}
@@ -0,0 +1,129 @@
+use bevy::prelude::World;
+
+use crate::runtime::UpdateFunc;
+
+use super::{Observer, UninitObserver};
+
+/// Combines a tuple of up to 12 observers into one returning a flat tuple of their values, which
+/// changes whenever any of them does. Works like chaining [`and`](super::ObserverExt::and),
+/// without the nesting.
+/// ```
+/// # use bevy::prelude::*;
+/// # use ui4::prelude::*;
+/// # struct Health(u32);
+/// # struct Mana(u32);
+/// # struct Gold(u32);
+/// fn stats(ctx: Ctx) -> Ctx {
+///     ctx.child(text(ui4::combine((res::<Health>(), res::<Mana>(), res::<Gold>())).map(
+///         |(health, mana, gold): (&Health, &Mana, &Gold)| {
+///             format!("{} hp, {} mp, {} gold", health.0, mana.0, gold.0)
+///         },
+///     )))
+/// }
+/// ```
+pub fn combine<T>(observers: T) -> Combine<T> {
+    Combine(observers)
+}
+
+#[derive(Clone, Copy)]
+pub struct Combine<T>(T);
+
+// Registers every observer in turn, each inside the callback of the previous one
+macro_rules! chain_register {
+    ($world:ident, $uf:ident, ($($done:ident),*);) => {
+        ($uf)(Combine(($($done,)*)), $world)
+    };
+    ($world:ident, $uf:ident, ($($done:ident),*); $next:ident $(, $rest:ident)*) => {
+        $next.register_self($world, move |$next, $world| {
+            chain_register!($world, $uf, ($($done,)* $next); $($rest),*)
+        })
+    };
+}
+
+macro_rules! impl_combine {
+    ($($item:ident),*) => {
+        #[allow(non_snake_case)]
+        impl<'a, $($item: Observer<'a>),*> Observer<'a> for Combine<($($item,)*)> {
+            type Return = ($($item::Return,)*);
+
+            fn get(&'a mut self, world: &'a World) -> (Self::Return, bool) {
+                let ($($item,)*) = &mut self.0;
+                let mut changed = false;
+                let ret = ($({
+                    let (val, c) = $item.get(world);
+                    changed |= c;
+                    val
+                },)*);
+                (ret, changed)
+            }
+        }
+
+        #[allow(non_snake_case)]
+        impl<$($item: UninitObserver),*> UninitObserver for Combine<($($item,)*)> {
+            type Observer = Combine<($($item::Observer,)*)>;
+
+            fn register_self<UF: FnOnce(Self::Observer, &mut World) -> UpdateFunc>(
+                self,
+                world: &mut World,
+                uf: UF,
+            ) -> UpdateFunc {
+                let ($($item,)*) = self.0;
+                chain_register!(world, uf, (); $($item),*)
+            }
+        }
+    };
+}
+
+impl_combine!(A, B);
+impl_combine!(A, B, C);
+impl_combine!(A, B, C, D);
+impl_combine!(A, B, C, D, E);
+impl_combine!(A, B, C, D, E, F);
+impl_combine!(A, B, C, D, E, F, G);
+impl_combine!(A, B, C, D, E, F, G, H);
+impl_combine!(A, B, C, D, E, F, G, H, I);
+impl_combine!(A, B, C, D, E, F, G, H, I, J);
+impl_combine!(A, B, C, D, E, F, G, H, I, J, K);
+impl_combine!(A, B, C, D, E, F, G, H, I, J, K, L);
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use bevy::prelude::*;
+
+    use crate::prelude::*;
+    use crate::testing::UiHarness;
+
+    struct A(i32);
+    struct B(i32);
+    struct C(i32);
+    struct Other;
+
+    #[test]
+    fn fires_when_any_observer_does() {
+        let log = Arc::new(Mutex::new(vec![]));
+        let log_c = log.clone();
+        let mut harness = UiHarness::new(Vec2::new(800., 600.), move |ctx: Ctx| {
+            ctx.world.insert_resource(A(1));
+            ctx.world.insert_resource(B(2));
+            ctx.world.insert_resource(C(3));
+            ctx.effect(
+                combine((res::<A>(), res::<B>(), res::<C>()))
+                    .map(|(a, b, c): (&A, &B, &C)| (a.0, b.0, c.0)),
+                move |values, _| log_c.lock().unwrap().push(values),
+            )
+        });
+
+        harness.world_mut().insert_resource(B(20));
+        harness.update();
+        harness.world_mut().insert_resource(C(30));
+        harness.update();
+        assert_eq!(*log.lock().unwrap(), [(1, 20, 3), (1, 20, 30)]);
+
+        harness.world_mut().insert_resource(Other);
+        harness.update();
+        harness.update();
+        assert_eq!(log.lock().unwrap().len(), 2);
+    }
+}
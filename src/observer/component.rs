@@ -3,6 +3,7 @@ use std::marker::PhantomData;
 use bevy::{ecs::prelude::*, utils::HashMap};
 
 use crate::{
+    debug::{record_dependency, DependencySource},
     lens::ComponentLens,
     runtime::{UfMarker, UiManagedSystems, UiScratchSpace, UpdateFunc},
 };
@@ -46,29 +47,42 @@ impl<T: Component> UninitObserver for ComponentObserver<T> {
     ) -> UpdateFunc {
         let uf = (uf)(self, world);
         let ufc = uf.clone();
-        world.resource_scope(|world, mut systems: Mut<UiManagedSystems>| {
+        let source = world.resource_scope(|world, mut systems: Mut<UiManagedSystems>| {
             if let Some(mut marker) = world.get_mut::<UfMarker<T>>(self.entity) {
+                let source = DependencySource::UpdateFunc(marker.update_func().downgrade());
                 marker.add_dependent(uf);
+                return source;
             } else if let Some(mut lists) = world.get_resource_mut::<ComponentUpdateFuncs<T>>() {
                 lists.0.entry(self.entity).or_default().push(uf);
             } else {
-                systems.0.add_system(component_change_track_system::<T>);
+                systems
+                    .add::<ComponentUpdateFuncs<T>, _>(world, component_change_track_system::<T>);
                 world.insert_resource(ComponentUpdateFuncs::<T>(
                     [(self.entity, vec![uf])].into_iter().collect(),
                     PhantomData,
                 ));
             };
+            DependencySource::Component {
+                entity: self.entity,
+                component: std::any::type_name::<T>(),
+            }
         });
+        record_dependency(world, source, &ufc);
         ufc
     }
 }
 
 fn component_change_track_system<T: Component>(
+    mut seen: Local<usize>,
     ui: Res<UiScratchSpace>,
     mut update_funcs: ResMut<ComponentUpdateFuncs<T>>,
     detector: Query<ChangeTrackers<T>>,
-) {
+) -> bool {
+    let prune = ui.any_died_since(&mut seen);
     update_funcs.0.retain(|entity, list| {
+        if prune {
+            list.retain(|uf| !uf.flagged());
+        }
         if let Ok(ticks) = detector.get(*entity) {
             if ticks.is_changed() {
                 ui.process_list(list);
@@ -78,6 +92,7 @@ fn component_change_track_system<T: Component>(
             false
         }
     });
+    !update_funcs.0.is_empty()
 }
 
 pub fn component<T: Component>(entity: Entity) -> ComponentLens<T> {
@@ -0,0 +1,176 @@
+use std::marker::PhantomData;
+
+use bevy::{ecs::prelude::*, transform::components::Parent};
+
+use crate::runtime::UpdateFunc;
+
+use super::{ComponentObserver, Observer, UninitObserver};
+
+/// A value provided to a widget and everything inside it, through [`Ctx::provide`](crate::ctx::Ctx::provide)
+#[derive(Component)]
+pub(crate) struct Provided<T>(pub(crate) T);
+
+pub struct UninitContextObserver<T>(pub(crate) Entity, pub(crate) PhantomData<T>);
+
+impl<T> Clone for UninitContextObserver<T> {
+    fn clone(&self) -> Self {
+        Self(self.0, PhantomData)
+    }
+}
+
+impl<T> Copy for UninitContextObserver<T> {}
+
+pub struct ContextObserver<T: Send + Sync + 'static>(ComponentObserver<Provided<T>>);
+
+pub struct UninitTryContextObserver<T>(pub(crate) Entity, pub(crate) PhantomData<T>);
+
+impl<T> Clone for UninitTryContextObserver<T> {
+    fn clone(&self) -> Self {
+        Self(self.0, PhantomData)
+    }
+}
+
+impl<T> Copy for UninitTryContextObserver<T> {}
+
+pub struct TryContextObserver<T: Send + Sync + 'static>(Option<ComponentObserver<Provided<T>>>);
+
+// Finds the closest ancestor of `entity` which provides a `T`, control nodes included
+fn find_provider<T: Send + Sync + 'static>(world: &World, entity: Entity) -> Option<Entity> {
+    let mut current = world.get::<Parent>(entity)?.0;
+    while world.get::<Provided<T>>(current).is_none() {
+        current = world.get::<Parent>(current)?.0;
+    }
+    Some(current)
+}
+
+impl<T: Send + Sync + 'static> UninitObserver for UninitContextObserver<T> {
+    type Observer = ContextObserver<T>;
+
+    fn register_self<F: FnOnce(Self::Observer, &mut World) -> UpdateFunc>(
+        self,
+        world: &mut World,
+        uf: F,
+    ) -> UpdateFunc {
+        let provider = find_provider::<T>(world, self.0).unwrap_or_else(|| {
+            panic!(
+                "No ancestor of {:?} provides a {}",
+                self.0,
+                std::any::type_name::<T>()
+            )
+        });
+        ComponentObserver::<Provided<T>> {
+            entity: provider,
+            _marker: PhantomData,
+        }
+        .register_self(world, |observer, world| {
+            uf(ContextObserver(observer), world)
+        })
+    }
+}
+
+impl<'a, T: Send + Sync + 'static> Observer<'a> for ContextObserver<T> {
+    type Return = &'a T;
+
+    fn get(&'a mut self, world: &'a World) -> (Self::Return, bool) {
+        let (provided, changed) = self.0.get(world);
+        (&provided.0, changed)
+    }
+}
+
+impl<T: Send + Sync + 'static> UninitObserver for UninitTryContextObserver<T> {
+    type Observer = TryContextObserver<T>;
+
+    fn register_self<F: FnOnce(Self::Observer, &mut World) -> UpdateFunc>(
+        self,
+        world: &mut World,
+        uf: F,
+    ) -> UpdateFunc {
+        match find_provider::<T>(world, self.0) {
+            Some(provider) => ComponentObserver::<Provided<T>> {
+                entity: provider,
+                _marker: PhantomData,
+            }
+            .register_self(world, |observer, world| {
+                uf(TryContextObserver(Some(observer)), world)
+            }),
+            // Providers are found once, so without one there's nothing to ever change
+            None => uf(TryContextObserver(None), world),
+        }
+    }
+}
+
+impl<'a, T: Send + Sync + 'static> Observer<'a> for TryContextObserver<T> {
+    type Return = Option<&'a T>;
+
+    fn get(&'a mut self, world: &'a World) -> (Self::Return, bool) {
+        match &mut self.0 {
+            Some(observer) => {
+                let (provided, changed) = observer.get(world);
+                (Some(&provided.0), changed)
+            }
+            None => (None, false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use bevy::prelude::*;
+
+    use crate::prelude::*;
+    use crate::testing::UiHarness;
+
+    struct Accent(i32);
+    struct Other;
+
+    #[derive(Clone)]
+    struct Theme(i32);
+    struct Missing(i32);
+
+    #[derive(Component)]
+    struct Seen(Option<i32>);
+
+    #[test]
+    fn context_follows_the_provider() {
+        let log = Arc::new(Mutex::new(vec![]));
+        let log_c = log.clone();
+        let mut harness = UiHarness::new(Vec2::new(800., 600.), move |ctx: Ctx| {
+            ctx.world.insert_resource(Accent(1));
+            ctx.provide(res::<Accent>().map(|a: &Accent| Theme(a.0)))
+                .child(move |ctx: Ctx| {
+                    let theme = ctx.context::<Theme>();
+                    ctx.effect(theme.map(|t: &Theme| t.0), move |accent, _| {
+                        log_c.lock().unwrap().push(accent)
+                    })
+                })
+                .child(|ctx: Ctx| {
+                    let theme = ctx.try_context::<Theme>();
+                    ctx.with(theme.map(|t: Option<&Theme>| Seen(t.map(|t| t.0))))
+                })
+                .child(|ctx: Ctx| {
+                    let missing = ctx.try_context::<Missing>();
+                    ctx.with(missing.map(|m: Option<&Missing>| Seen(m.map(|m| m.0))))
+                })
+        });
+        let root = harness.root().entity();
+        let widget = harness.world().get::<Children>(root).unwrap()[0];
+        let children = harness.world().get::<Children>(widget).unwrap().to_vec();
+        let seen =
+            |harness: &UiHarness, i: usize| harness.world().get::<Seen>(children[i]).unwrap().0;
+        assert_eq!(seen(&harness, 1), Some(1));
+        assert_eq!(seen(&harness, 2), None);
+
+        harness.world_mut().insert_resource(Accent(2));
+        harness.update();
+        assert_eq!(*log.lock().unwrap(), [2]);
+        assert_eq!(seen(&harness, 1), Some(2));
+        assert_eq!(seen(&harness, 2), None);
+
+        harness.world_mut().insert_resource(Other);
+        harness.update();
+        harness.update();
+        assert_eq!(*log.lock().unwrap(), [2]);
+    }
+}
@@ -0,0 +1,144 @@
+use std::marker::PhantomData;
+
+use bevy::ecs::{event::ManualEventReader, prelude::*};
+
+use crate::debug::{record_dependency, DependencySource};
+use crate::runtime::{UiManagedSystems, UiScratchSpace, UpdateFunc};
+
+use super::{Observer, UninitObserver};
+
+struct EventUpdateFuncs<E> {
+    list: Vec<UpdateFunc>,
+    // Kept here rather than in the system, so a registry made after the last one emptied out
+    // starts reading at the current end of the buffer instead of getting stale events again
+    reader: ManualEventReader<E>,
+    // The events sent since the last flush
+    events: Vec<E>,
+}
+
+pub struct EventObserver<E>(PhantomData<E>);
+
+impl<E> Clone for EventObserver<E> {
+    fn clone(&self) -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<E> Copy for EventObserver<E> {}
+
+impl<'a, E: Send + Sync + Clone + 'static> Observer<'a> for EventObserver<E> {
+    type Return = &'a [E];
+
+    fn get(&'a mut self, world: &'a World) -> (Self::Return, bool) {
+        let events = &world.get_resource::<EventUpdateFuncs<E>>().unwrap().events;
+        (events, !events.is_empty())
+    }
+}
+
+impl<E: Send + Sync + Clone + 'static> UninitObserver for EventObserver<E> {
+    type Observer = Self;
+
+    fn register_self<F: FnOnce(Self::Observer, &mut World) -> UpdateFunc>(
+        self,
+        world: &mut World,
+        uf: F,
+    ) -> UpdateFunc {
+        let uf = uf(self, world);
+        let ufc = uf.clone();
+        world.resource_scope(|world, mut systems: Mut<UiManagedSystems>| {
+            if let Some(mut registry) = world.get_resource_mut::<EventUpdateFuncs<E>>() {
+                registry.list.push(uf);
+            } else {
+                systems.add::<EventUpdateFuncs<E>, _>(world, event_track_system::<E>);
+                let reader = world
+                    .get_resource::<Events<E>>()
+                    .expect("observing events requires adding them to the app")
+                    .get_reader_current();
+                world.insert_resource(EventUpdateFuncs::<E> {
+                    list: vec![uf],
+                    reader,
+                    events: vec![],
+                });
+            };
+        });
+        record_dependency(
+            world,
+            DependencySource::Event(std::any::type_name::<E>()),
+            &ufc,
+        );
+        ufc
+    }
+}
+
+/// Gets an observer for the events of type `E`, which fires whenever new ones are sent and
+/// returns all of them as a slice. Use `.map(|e| e.last().cloned())` if you only care about the
+/// latest one.
+pub fn event<E: Send + Sync + Clone + 'static>() -> EventObserver<E> {
+    EventObserver(PhantomData)
+}
+
+fn event_track_system<E: Send + Sync + Clone + 'static>(
+    mut seen: Local<usize>,
+    ui: Res<UiScratchSpace>,
+    mut registry: ResMut<EventUpdateFuncs<E>>,
+    events: Res<Events<E>>,
+) -> bool {
+    let registry = &mut *registry;
+    registry.events.clear();
+    registry
+        .events
+        .extend(registry.reader.iter(&events).cloned());
+    if !registry.events.is_empty() {
+        ui.process_list(&mut registry.list);
+    } else if ui.any_died_since(&mut seen) {
+        registry.list.retain(|uf| !uf.flagged());
+    }
+    !registry.list.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use bevy::prelude::*;
+
+    use crate::prelude::*;
+    use crate::testing::UiHarness;
+
+    #[derive(Clone)]
+    struct Ping(i32);
+
+    #[derive(Component)]
+    struct Received(i32);
+
+    #[test]
+    fn fires_only_for_new_events() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let runs_c = runs.clone();
+        let mut harness = UiHarness::new(Vec2::new(800., 600.), move |ctx: Ctx| {
+            ctx.world.insert_resource(Events::<Ping>::default());
+            ctx.with(event::<Ping>().map(move |pings: &[Ping]| {
+                runs_c.fetch_add(1, Ordering::Relaxed);
+                Received(pings.iter().map(|p| p.0).sum())
+            }))
+        });
+        let root = harness.root().entity();
+        let widget = harness.world().get::<Children>(root).unwrap()[0];
+        runs.store(0, Ordering::Relaxed);
+
+        let mut events = harness
+            .world_mut()
+            .get_resource_mut::<Events<Ping>>()
+            .unwrap();
+        events.send(Ping(2));
+        events.send(Ping(3));
+        harness.update();
+        assert_eq!(runs.load(Ordering::Relaxed), 1);
+        assert_eq!(harness.world().get::<Received>(widget).unwrap().0, 5);
+
+        harness.update();
+        harness.update();
+        assert_eq!(runs.load(Ordering::Relaxed), 1);
+    }
+}
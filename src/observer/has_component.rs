@@ -5,6 +5,7 @@ use bevy::{
     utils::HashMap,
 };
 
+use crate::debug::{record_dependency, DependencySource};
 use crate::runtime::{UiManagedSystems, UiScratchSpace, UpdateFunc};
 
 use super::{Observer, UninitObserver};
@@ -47,23 +48,39 @@ impl<T: Component> UninitObserver for ComponentExistsObserver<T> {
             if let Some(mut lists) = world.get_resource_mut::<ComponentExistsUpdateFuncs<T>>() {
                 lists.0.entry(self.0).or_default().push(uf);
             } else {
-                systems.0.add_system(component_exist_track_system::<T>);
+                systems.add::<ComponentExistsUpdateFuncs<T>, _>(
+                    world,
+                    component_exist_track_system::<T>,
+                );
                 world.insert_resource(ComponentExistsUpdateFuncs::<T>(
                     [(self.0, vec![uf])].into_iter().collect(),
                     PhantomData,
                 ));
             };
         });
+        record_dependency(
+            world,
+            DependencySource::ComponentExists {
+                entity: self.0,
+                component: std::any::type_name::<T>(),
+            },
+            &ufc,
+        );
         ufc
     }
 }
 
 fn component_exist_track_system<T: Component>(
+    mut seen: Local<usize>,
     ui: Res<UiScratchSpace>,
     mut update_funcs: ResMut<ComponentExistsUpdateFuncs<T>>,
     detector: Query<ChangeTrackers<T>>,
-) {
+) -> bool {
+    let prune = ui.any_died_since(&mut seen);
     update_funcs.0.retain(|entity, list| {
+        if prune {
+            list.retain(|uf| !uf.flagged());
+        }
         match detector.get(*entity) {
             Ok(ticks) if ticks.is_added() => ui.process_list(list),
             Err(QueryEntityError::QueryDoesNotMatch) => ui.process_list(list),
@@ -73,4 +90,5 @@ fn component_exist_track_system<T: Component>(
         }
         !list.is_empty()
     });
+    !update_funcs.0.is_empty()
 }
@@ -8,17 +8,38 @@ use bevy::prelude::World;
 use crate::runtime::UpdateFunc;
 use crate::{Dynamic, Static};
 
+mod asset;
+mod combine;
 mod component;
+mod context;
+mod event;
 mod has_component;
+mod node_layout;
 mod opt_component;
+mod query;
 mod res;
+mod shared;
 mod single;
+mod task;
+mod timing;
+mod window;
 
 pub use {
-    component::component, component::ComponentObserver, has_component::ComponentExistsObserver,
-    opt_component::OptComponentObserver, res::res, single::single,
+    asset::asset, asset::AssetObserver, combine::combine, combine::Combine, component::component,
+    component::ComponentObserver, context::ContextObserver, context::TryContextObserver,
+    context::UninitContextObserver, context::UninitTryContextObserver, event::event,
+    event::EventObserver, has_component::ComponentExistsObserver, node_layout::LayoutObserver,
+    node_layout::NodeLayout, opt_component::OptComponentObserver, query::query,
+    query::QueryObserver, query::UninitQueryObserver, res::res, shared::SharedObserver,
+    shared::SharedTemplate, single::opt_single, single::single, task::task, task::try_task,
+    task::TaskObserver, task::TaskStatus, task::UninitTaskObserver, timing::interval,
+    timing::IntervalObserver, timing::Timed, timing::TimedTemplate, window::scale_factor,
+    window::window_focused, window::window_size, window::WindowObserver,
 };
 
+pub(crate) use context::Provided;
+pub(crate) use timing::waker_system;
+
 /// Types implementing this trait represent a mapping from world and internal state to a certain output.
 pub trait UninitObserver: Send + Sync + 'static {
     #[doc(hidden)]
@@ -213,7 +234,7 @@ impl<O1: UninitObserver, O2: UninitObserver> UninitObserver for And<O1, O2> {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Copy)]
 pub struct DedupTemplate<O>(O);
 
 pub struct Dedup<O: for<'a> Observer<'a>>(Option<<O as Observer<'static>>::Return>, O);
@@ -258,6 +279,166 @@ where
     }
 }
 
+#[derive(Clone)]
+pub struct ScanTemplate<O, S, F>(O, S, F);
+
+pub struct Scan<O, S, F> {
+    observer: O,
+    state: S,
+    f: F,
+    initialized: bool,
+}
+
+impl<UO, O, T, S, SF> UninitObserver for ScanTemplate<UO, S, SF>
+where
+    UO: UninitObserver<Observer = O>,
+    O: for<'a> Observer<'a, Return = T>,
+    S: Send + Sync + 'static,
+    SF: Fn(&mut S, T) + Send + Sync + 'static,
+{
+    type Observer = Scan<O, S, SF>;
+
+    fn register_self<F: FnOnce(Self::Observer, &mut World) -> UpdateFunc>(
+        self,
+        world: &mut World,
+        uf: F,
+    ) -> UpdateFunc {
+        let (state, f) = (self.1, self.2);
+        self.0.register_self(world, move |observer, world| {
+            (uf)(
+                Scan {
+                    observer,
+                    state,
+                    f,
+                    initialized: false,
+                },
+                world,
+            )
+        })
+    }
+}
+
+impl<'a, O, T, S, F> Observer<'a> for Scan<O, S, F>
+where
+    O: for<'x> Observer<'x, Return = T>,
+    S: Send + Sync + 'static,
+    F: Fn(&mut S, T) + Send + Sync + 'static,
+{
+    type Return = &'a S;
+
+    fn get(&'a mut self, world: &'a World) -> (Self::Return, bool) {
+        let (val, changed) = self.observer.get(world);
+        let changed = changed || !self.initialized;
+        if changed {
+            self.initialized = true;
+            (self.f)(&mut self.state, val);
+        }
+        (&self.state, changed)
+    }
+}
+
+#[derive(Clone)]
+pub struct WithPreviousTemplate<O>(O);
+
+pub struct WithPrevious<O: for<'a> Observer<'a>> {
+    observer: O,
+    previous: Option<<O as Observer<'static>>::Return>,
+    current: Option<<O as Observer<'static>>::Return>,
+}
+
+impl<UO, O, T> UninitObserver for WithPreviousTemplate<UO>
+where
+    UO: UninitObserver<Observer = O>,
+    O: for<'a> Observer<'a, Return = T>,
+    T: PartialEq + Send + Sync + 'static,
+{
+    type Observer = WithPrevious<O>;
+
+    fn register_self<F: FnOnce(Self::Observer, &mut World) -> UpdateFunc>(
+        self,
+        world: &mut World,
+        uf: F,
+    ) -> UpdateFunc {
+        self.0.register_self(world, |observer, world| {
+            (uf)(
+                WithPrevious {
+                    observer,
+                    previous: None,
+                    current: None,
+                },
+                world,
+            )
+        })
+    }
+}
+
+impl<'a, O, T> Observer<'a> for WithPrevious<O>
+where
+    O: for<'x> Observer<'x, Return = T>,
+    T: PartialEq + Send + Sync + 'static,
+{
+    type Return = (Option<&'a T>, &'a T);
+
+    fn get(&'a mut self, world: &'a World) -> (Self::Return, bool) {
+        let (val, changed) = self.observer.get(world);
+        // Upstream may report a change without the value changing, which would make the previous
+        // value the same as the current one
+        let changed = self.current.is_none() || (changed && self.current.as_ref() != Some(&val));
+        if changed {
+            self.previous = self.current.replace(val);
+        }
+        (
+            (self.previous.as_ref(), self.current.as_ref().unwrap()),
+            changed,
+        )
+    }
+}
+
+#[derive(Clone)]
+pub struct FilterTemplate<O, P>(O, P);
+
+pub struct Filter<O: for<'a> Observer<'a>, P>(Option<<O as Observer<'static>>::Return>, O, P);
+
+impl<UO, O, T, P> UninitObserver for FilterTemplate<UO, P>
+where
+    UO: UninitObserver<Observer = O>,
+    O: for<'a> Observer<'a, Return = T>,
+    T: Send + Sync + 'static,
+    P: Fn(&T) -> bool + Send + Sync + 'static,
+{
+    type Observer = Filter<O, P>;
+
+    fn register_self<F: FnOnce(Self::Observer, &mut World) -> UpdateFunc>(
+        self,
+        world: &mut World,
+        uf: F,
+    ) -> UpdateFunc {
+        let pred = self.1;
+        self.0.register_self(world, move |obs, world| {
+            (uf)(Filter(None, obs, pred), world)
+        })
+    }
+}
+
+impl<'a, O, T, P> Observer<'a> for Filter<O, P>
+where
+    O: for<'x> Observer<'x, Return = T>,
+    T: Send + Sync + 'static,
+    P: Fn(&T) -> bool + Send + Sync + 'static,
+{
+    type Return = &'a T;
+
+    fn get(&'a mut self, world: &'a World) -> (Self::Return, bool) {
+        let (val, changed) = self.1.get(world);
+        // There has to be something to return, so the first value goes through either way
+        let changed = self.0.is_none() || (changed && (self.2)(&val));
+        if changed {
+            self.0 = Some(val);
+        }
+        (self.0.as_ref().unwrap(), changed)
+    }
+}
+
 #[derive(Clone)]
 pub struct FlattenTemplate<O>(O);
 
@@ -285,18 +466,21 @@ where
                 uf(Flatten(arc.clone(), None), world)
             });
 
-            let (uf, marker) = UpdateFunc::new::<(), _>(move |world| {
-                let (uo2, changed) = obs.get(world);
-                if changed {
-                    uo2.register_self(world, |o2, _world| {
-                        *arc.lock().unwrap() = Some(o2);
-                        inner_uf.clone()
-                    });
-                    inner_uf.run(world);
-                }
-            });
-            marker.forget();
-            uf
+            let owner = inner_uf.clone();
+            UpdateFunc::new_tied::<(), _>(
+                world,
+                move |world| {
+                    let (uo2, changed) = obs.get(world);
+                    if changed {
+                        uo2.register_self(world, |o2, _world| {
+                            *arc.lock().unwrap() = Some(o2);
+                            inner_uf.clone()
+                        });
+                        inner_uf.run(world);
+                    }
+                },
+                &owner,
+            )
         })
     }
 }
@@ -394,6 +578,112 @@ pub trait ObserverExt: UninitObserver + Sized {
     {
         FlattenTemplate(self)
     }
+
+    /// Holds changes back until the value has stopped changing for `seconds`, then passes on
+    /// the latest one. Panics if `seconds` isn't positive.
+    fn debounce<T>(self, seconds: f32) -> TimedTemplate<Self>
+    where
+        <Self as UninitObserver>::Observer: for<'a> Observer<'a, Return = T>,
+        T: Send + Sync + 'static,
+    {
+        TimedTemplate::debounce(self, seconds)
+    }
+
+    /// Passes on at most one change every `seconds`. Changes coming in faster are merged, and the
+    /// latest one goes through once the time is up. Panics if `seconds` isn't positive.
+    fn throttle<T>(self, seconds: f32) -> TimedTemplate<Self>
+    where
+        <Self as UninitObserver>::Observer: for<'a> Observer<'a, Return = T>,
+        T: Send + Sync + 'static,
+    {
+        TimedTemplate::throttle(self, seconds)
+    }
+
+    /// Passes on every change `seconds` after it happened. Panics if `seconds` isn't positive.
+    fn delay<T>(self, seconds: f32) -> TimedTemplate<Self>
+    where
+        <Self as UninitObserver>::Observer: for<'a> Observer<'a, Return = T>,
+        T: Send + Sync + 'static,
+    {
+        TimedTemplate::delay(self, seconds)
+    }
+
+    /// Folds every value into an accumulator, starting with `init`, and returns the accumulator.
+    ///
+    /// The values are moved into `f`, so observers returning references need a `.map`,
+    /// `.copied()` or `.cloned()` first. Every change reported upstream is folded in, even if the
+    /// value is the same as before, which some observers (like [`component`]) report every time
+    /// anything might have changed. Put `.dedup()` upstream if that matters.
+    fn scan<S, F, T>(self, init: S, f: F) -> ScanTemplate<Self, S, F>
+    where
+        <Self as UninitObserver>::Observer: for<'a> Observer<'a, Return = T>,
+        S: Send + Sync + 'static,
+        F: Fn(&mut S, T) + Send + Sync + 'static,
+    {
+        ScanTemplate(self, init, f)
+    }
+
+    /// Returns the previous value along with the current one, which is `None` the first time.
+    /// Changes leaving the value equal to the current one are ignored, so the two are never the
+    /// same. The values are kept around, so observers returning references need a `.map`,
+    /// `.copied()` or `.cloned()` first.
+    /// ```
+    /// # use bevy::prelude::*;
+    /// # use ui4::prelude::*;
+    /// # #[derive(Component)] struct Hp(u32);
+    /// # fn hp_bar(ctx: Ctx, player: Entity) -> Ctx {
+    /// ctx.with(component::<Hp>(player).map(|hp: &Hp| hp.0).with_previous().map(
+    ///     |(old, new): (Option<&u32>, &u32)| {
+    ///         UiColor(match old {
+    ///             Some(old) if new < old => Color::RED,
+    ///             _ => Color::WHITE,
+    ///         })
+    ///     },
+    /// ))
+    /// # }
+    /// ```
+    fn with_previous<T>(self) -> WithPreviousTemplate<Self>
+    where
+        <Self as UninitObserver>::Observer: for<'a> Observer<'a, Return = T>,
+        T: PartialEq + Send + Sync + 'static,
+    {
+        WithPreviousTemplate(self)
+    }
+
+    /// Only passes on changes for which `pred` returns true. The first value always goes through.
+    ///
+    /// The values are kept around, so observers returning references need a `.map`, `.copied()`
+    /// or `.cloned()` first. Like [`scan`](Self::scan), `pred` sees every change reported
+    /// upstream, including ones where the value stayed the same.
+    fn filter<T, P>(self, pred: P) -> FilterTemplate<Self, P>
+    where
+        <Self as UninitObserver>::Observer: for<'a> Observer<'a, Return = T>,
+        T: Send + Sync + 'static,
+        P: Fn(&T) -> bool + Send + Sync + 'static,
+    {
+        FilterTemplate(self, pred)
+    }
+
+    /// Computes the value once per flush, no matter how many widgets use it. Clones of the
+    /// returned observer all share that computation, which stops once none of them are in use.
+    /// ```
+    /// # use bevy::prelude::*;
+    /// # use ui4::prelude::*;
+    /// # struct Inventory(Vec<u32>);
+    /// fn totals(ctx: Ctx) -> Ctx {
+    ///     let total = res::<Inventory>().map(|inv: &Inventory| inv.0.iter().sum::<u32>()).share();
+    ///     ctx.child(text(total.clone().map(|t: &u32| format!("{} items", t))))
+    ///         .child(text(total.map(|t: &u32| format!("{} gold", t * 10))))
+    /// }
+    /// ```
+    fn share<T>(self) -> SharedTemplate<Self, T>
+    where
+        Self: Clone,
+        <Self as UninitObserver>::Observer: for<'a> Observer<'a, Return = T>,
+        T: Send + Sync + 'static,
+    {
+        SharedTemplate::new(self)
+    }
 }
 
 impl<T: UninitObserver> ObserverExt for T {}
@@ -464,3 +754,78 @@ impl<T: 'static, O: for<'a> Observer<'a, Return = T>, UO: UninitObserver<Observe
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use bevy::prelude::*;
+
+    use super::{Observer, UninitObserver};
+    use crate::prelude::*;
+    use crate::testing::UiHarness;
+
+    struct Num(i32);
+    struct Other;
+
+    // Builds a widget logging every value `observer` fires with after the first, and sets `Num`
+    // to each of `values` in turn
+    fn log_changes<O, T>(observer: O, values: &[i32]) -> Vec<T>
+    where
+        O: UninitObserver,
+        for<'a> O::Observer: Observer<'a, Return = T>,
+        T: Send + 'static,
+    {
+        let log = Arc::new(Mutex::new(vec![]));
+        let log_c = log.clone();
+        let mut harness = UiHarness::new(Vec2::new(800., 600.), move |ctx: Ctx| {
+            ctx.world.insert_resource(Num(1));
+            ctx.effect(observer, move |val, _| log_c.lock().unwrap().push(val))
+        });
+        for &value in values {
+            harness.world_mut().insert_resource(Num(value));
+            harness.update();
+            // Neither unrelated changes nor quiet frames fire anything
+            harness.world_mut().insert_resource(Other);
+            harness.update();
+        }
+        let mut log = log.lock().unwrap();
+        std::mem::take(&mut *log)
+    }
+
+    #[test]
+    fn scan_folds_every_change() {
+        let log = log_changes(
+            res::<Num>()
+                .map(|n: &Num| n.0)
+                .scan(0, |acc: &mut i32, n: i32| *acc += n)
+                .copied(),
+            &[2, 3],
+        );
+        assert_eq!(log, [3, 6]);
+    }
+
+    #[test]
+    fn with_previous_skips_unchanged_values() {
+        let log = log_changes(
+            res::<Num>()
+                .map(|n: &Num| n.0)
+                .with_previous()
+                .map(|(old, new): (Option<&i32>, &i32)| (old.copied(), *new)),
+            &[2, 2, 3],
+        );
+        assert_eq!(log, [(Some(1), 2), (Some(2), 3)]);
+    }
+
+    #[test]
+    fn filter_drops_rejected_changes() {
+        let log = log_changes(
+            res::<Num>()
+                .map(|n: &Num| n.0)
+                .filter(|n: &i32| n % 2 == 0)
+                .copied(),
+            &[2, 3, 4],
+        );
+        assert_eq!(log, [2, 4]);
+    }
+}
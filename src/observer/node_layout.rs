@@ -0,0 +1,158 @@
+use bevy::{ecs::prelude::*, math::Vec2, utils::HashMap};
+
+use crate::{
+    debug::{record_dependency, DependencySource},
+    dom::{ClippedNode, Node},
+    runtime::{UiManagedSystems, UiScratchSpace, UpdateFunc},
+};
+
+use super::{Observer, UninitObserver};
+
+/// The layout computed for a node, in logical pixels from the top left corner of the window
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct NodeLayout {
+    pub pos: Vec2,
+    pub size: Vec2,
+    /// The top left corner of the part of the node left visible by the clipping of its ancestors
+    pub clip_min: Vec2,
+    /// The bottom right corner of the part of the node left visible by the clipping of its
+    /// ancestors
+    pub clip_max: Vec2,
+}
+
+fn read_layout(world: &World, entity: Entity) -> NodeLayout {
+    let e = world.entity(entity);
+    match (e.get::<Node>(), e.get::<ClippedNode>()) {
+        (Some(node), Some(clipped)) => NodeLayout {
+            pos: node.pos,
+            size: node.size,
+            clip_min: clipped.min,
+            clip_max: clipped.max,
+        },
+        _ => NodeLayout::default(),
+    }
+}
+
+// The layout of every observed node as of the last flush, so only actual changes trigger anything
+struct LayoutUpdateFuncs(HashMap<Entity, (NodeLayout, Vec<UpdateFunc>)>);
+
+#[derive(Clone, Copy)]
+pub struct LayoutObserver {
+    pub(crate) entity: Entity,
+    pub(crate) last: Option<NodeLayout>,
+}
+
+impl<'a> Observer<'a> for LayoutObserver {
+    type Return = NodeLayout;
+
+    fn get(&'a mut self, world: &'a World) -> (Self::Return, bool) {
+        let layout = read_layout(world, self.entity);
+        let changed = self.last != Some(layout);
+        self.last = Some(layout);
+        (layout, changed)
+    }
+}
+
+impl UninitObserver for LayoutObserver {
+    type Observer = Self;
+
+    fn register_self<F: FnOnce(Self::Observer, &mut World) -> UpdateFunc>(
+        self,
+        world: &mut World,
+        uf: F,
+    ) -> UpdateFunc {
+        let entity = self.entity;
+        let uf = uf(self, world);
+        let ufc = uf.clone();
+        let layout = read_layout(world, entity);
+        world.resource_scope(|world, mut systems: Mut<UiManagedSystems>| {
+            if let Some(mut registry) = world.get_resource_mut::<LayoutUpdateFuncs>() {
+                registry
+                    .0
+                    .entry(entity)
+                    .or_insert_with(|| (layout, vec![]))
+                    .1
+                    .push(uf);
+            } else {
+                systems.add::<LayoutUpdateFuncs, _>(world, layout_track_system);
+                world.insert_resource(LayoutUpdateFuncs(
+                    [(entity, (layout, vec![uf]))].into_iter().collect(),
+                ));
+            };
+        });
+        record_dependency(world, DependencySource::Layout(entity), &ufc);
+        ufc
+    }
+}
+
+fn layout_track_system(
+    mut seen: Local<usize>,
+    ui: Res<UiScratchSpace>,
+    mut registry: ResMut<LayoutUpdateFuncs>,
+    nodes: Query<(&Node, &ClippedNode)>,
+) -> bool {
+    let prune = ui.any_died_since(&mut seen);
+    registry.0.retain(|&entity, (last, list)| {
+        let (node, clipped) = if let Ok(node) = nodes.get(entity) {
+            node
+        } else {
+            return false;
+        };
+        let layout = NodeLayout {
+            pos: node.pos,
+            size: node.size,
+            clip_min: clipped.min,
+            clip_max: clipped.max,
+        };
+        if layout != *last {
+            *last = layout;
+            ui.process_list(list);
+        } else if prune {
+            list.retain(|uf| !uf.flagged());
+        }
+        !list.is_empty()
+    });
+    !registry.0.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use bevy::prelude::*;
+
+    use crate::prelude::*;
+    use crate::testing::UiHarness;
+
+    struct Size(f32);
+    struct Other(i32);
+
+    #[test]
+    fn fires_when_the_layout_changes() {
+        let widths = Arc::new(Mutex::new(vec![]));
+        let widths_c = widths.clone();
+        let mut harness = UiHarness::new(Vec2::new(800., 600.), move |ctx: Ctx| {
+            ctx.world.insert_resource(Size(200.));
+            ctx.world.insert_resource(Other(0));
+            let layout = ctx.layout();
+            ctx.with(res::<Size>().map(|s: &Size| Width(Units::Pixels(s.0))))
+                .effect(layout, move |layout: NodeLayout, _| {
+                    widths_c.lock().unwrap().push(layout.size.x);
+                })
+        });
+        harness.update();
+        harness.update();
+        widths.lock().unwrap().clear();
+
+        // The new width is laid out after the update that sets it, and seen on the next one
+        harness.world_mut().insert_resource(Size(300.));
+        harness.update();
+        harness.update();
+        assert_eq!(*widths.lock().unwrap(), [300.]);
+
+        harness.world_mut().insert_resource(Other(1));
+        harness.update();
+        harness.update();
+        assert_eq!(*widths.lock().unwrap(), [300.]);
+    }
+}
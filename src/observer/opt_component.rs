@@ -5,6 +5,7 @@ use bevy::{
     utils::HashMap,
 };
 
+use crate::debug::{record_dependency, DependencySource};
 use crate::runtime::{UfMarker, UiManagedSystems, UiScratchSpace, UpdateFunc};
 
 use super::{Observer, UninitObserver};
@@ -43,29 +44,44 @@ impl<T: Component> UninitObserver for OptComponentObserver<T> {
     ) -> UpdateFunc {
         let uf = (uf)(self, world);
         let ufc = uf.clone();
-        world.resource_scope(|world, mut systems: Mut<UiManagedSystems>| {
+        let source = world.resource_scope(|world, mut systems: Mut<UiManagedSystems>| {
             if let Some(mut marker) = world.get_mut::<UfMarker<T>>(self.0) {
+                let source = DependencySource::UpdateFunc(marker.update_func().downgrade());
                 marker.add_dependent(uf);
+                return source;
             } else if let Some(mut lists) = world.get_resource_mut::<OptComponentUpdateFuncs<T>>() {
                 lists.0.entry(self.0).or_default().push(uf);
             } else {
-                systems.0.add_system(opt_component_change_track_system::<T>);
+                systems.add::<OptComponentUpdateFuncs<T>, _>(
+                    world,
+                    opt_component_change_track_system::<T>,
+                );
                 world.insert_resource(OptComponentUpdateFuncs::<T>(
                     [(self.0, vec![uf])].into_iter().collect(),
                     PhantomData,
                 ));
             };
+            DependencySource::OptComponent {
+                entity: self.0,
+                component: std::any::type_name::<T>(),
+            }
         });
+        record_dependency(world, source, &ufc);
         ufc
     }
 }
 
 fn opt_component_change_track_system<T: Component>(
+    mut seen: Local<usize>,
     ui: Res<UiScratchSpace>,
     mut update_funcs: ResMut<OptComponentUpdateFuncs<T>>,
     detector: Query<ChangeTrackers<T>>,
-) {
+) -> bool {
+    let prune = ui.any_died_since(&mut seen);
     update_funcs.0.retain(|entity, list| {
+        if prune {
+            list.retain(|uf| !uf.flagged());
+        }
         match detector.get(*entity) {
             Ok(ticks) if ticks.is_changed() => ui.process_list(list),
             Err(QueryEntityError::QueryDoesNotMatch) => ui.process_list(list),
@@ -75,4 +91,5 @@ fn opt_component_change_track_system<T: Component>(
         }
         !list.is_empty()
     });
+    !update_funcs.0.is_empty()
 }
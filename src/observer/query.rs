@@ -0,0 +1,276 @@
+use std::marker::PhantomData;
+
+use bevy::{
+    ecs::{
+        prelude::*,
+        query::{Fetch, FilterFetch, ReadOnlyFetch, WorldQuery},
+    },
+    prelude::{BuildWorldChildren, DespawnRecursiveExt, QueryState},
+    utils::{HashMap, HashSet},
+};
+
+use crate::{
+    animation::{trigger_transition_out_cn, TriggerCallState},
+    childable::Childable,
+    ctx::Ctx,
+    debug::{record_dependency, DependencySource},
+    dom::ControlBundle,
+    runtime::{UiManagedSystems, UiScratchSpace, UpdateFunc},
+};
+
+use super::{single::SingleObserverTuple, Observer, UninitObserver};
+
+struct QueryUpdateFuncs<T, F>(Vec<UpdateFunc>, PhantomData<fn() -> (T, F)>);
+
+pub struct UninitQueryObserver<T, F>(PhantomData<fn() -> (T, F)>);
+pub struct QueryObserver<T: SingleObserverTuple, F: WorldQuery>(
+    QueryState<(Entity, T::DataQuery), F>,
+);
+
+/// Gets an observer for every entity matching a query, yielding the entities along with their
+/// components. It fires whenever any of the components change, or entities start or stop
+/// matching.
+/// ```
+/// # use bevy::prelude::*;
+/// # use ui4::prelude::*;
+/// # #[derive(Component)] struct Health(u32);
+/// # #[derive(Component)] struct Enemy;
+/// fn enemy_count(ctx: Ctx) -> Ctx {
+///     ctx.child(text(query::<(Health,), With<Enemy>>().map(
+///         |enemies: Vec<(Entity, (&Health,))>| format!("{} enemies left", enemies.len()),
+///     )))
+/// }
+/// ```
+pub fn query<T: SingleObserverTuple, F: WorldQuery + 'static>() -> UninitQueryObserver<T, F>
+where
+    F::Fetch: FilterFetch,
+{
+    UninitQueryObserver(PhantomData)
+}
+
+impl<T: SingleObserverTuple, F: WorldQuery + 'static> UninitObserver for UninitQueryObserver<T, F>
+where
+    F::Fetch: FilterFetch,
+    <T::DataQuery as WorldQuery>::Fetch: ReadOnlyFetch,
+    <T::ChangeDetectionQuery as WorldQuery>::Fetch: ReadOnlyFetch,
+{
+    type Observer = QueryObserver<T, F>;
+
+    fn register_self<UF: FnOnce(Self::Observer, &mut World) -> UpdateFunc>(
+        self,
+        world: &mut World,
+        uf: UF,
+    ) -> UpdateFunc {
+        let uf = uf(QueryObserver(world.query_filtered()), world);
+        world.resource_scope(|world, mut systems: Mut<UiManagedSystems>| {
+            if let Some(mut list) = world.get_resource_mut::<QueryUpdateFuncs<T, F>>() {
+                list.0.push(uf.clone());
+            } else {
+                systems.add::<QueryUpdateFuncs<T, F>, _>(world, query_change_track_system::<T, F>);
+                world.insert_resource(QueryUpdateFuncs::<T, F>(vec![uf.clone()], PhantomData));
+            };
+        });
+        record_dependency(
+            world,
+            DependencySource::Query(std::any::type_name::<(T, F)>()),
+            &uf,
+        );
+        uf
+    }
+}
+
+impl<'a, T: SingleObserverTuple, F: WorldQuery + 'static> Observer<'a> for QueryObserver<T, F>
+where
+    F::Fetch: FilterFetch,
+    <T::DataQuery as WorldQuery>::Fetch: ReadOnlyFetch,
+{
+    type Return = Vec<(
+        Entity,
+        <<T::DataQuery as WorldQuery>::ReadOnlyFetch as Fetch<'a, 'a>>::Item,
+    )>;
+
+    fn get(&'a mut self, world: &'a World) -> (Self::Return, bool) {
+        (self.0.iter(world).collect(), true)
+    }
+}
+
+fn query_change_track_system<T: SingleObserverTuple, F: WorldQuery + 'static>(
+    mut seen: Local<usize>,
+    mut matched: Local<Vec<Entity>>,
+    q: Query<(Entity, T::ChangeDetectionQuery), F>,
+    mut list: ResMut<QueryUpdateFuncs<T, F>>,
+    ui: Res<UiScratchSpace>,
+) -> bool
+where
+    F::Fetch: FilterFetch,
+    <T::ChangeDetectionQuery as WorldQuery>::Fetch: ReadOnlyFetch,
+{
+    let mut changed = false;
+    let mut count = 0;
+    for (i, (entity, flags)) in q.iter().enumerate() {
+        count = i + 1;
+        if matched.get(i) != Some(&entity) {
+            changed = true;
+            matched.truncate(i);
+            matched.push(entity);
+        }
+        changed |= T::get_changed(&flags);
+    }
+    if matched.len() != count {
+        changed = true;
+        matched.truncate(count);
+    }
+
+    if changed {
+        ui.process_list(&mut list.0);
+    } else if ui.any_died_since(&mut seen) {
+        list.0.retain(|uf| !uf.flagged());
+    }
+    !list.0.is_empty()
+}
+
+struct QueryForeachUfMarker;
+
+#[doc(hidden)]
+pub struct QueryMarker;
+
+pub struct QueryForeach<T, F, MF>(UninitQueryObserver<T, F>, MF);
+
+impl<T, F> UninitQueryObserver<T, F> {
+    /// Builds children for every matching entity. The children of an entity are built once when
+    /// it starts matching, and removed (after their out transitions) when it stops, so use
+    /// observers on the entity to keep them up to date.
+    pub fn each<MF, C, M>(self, f: MF) -> QueryForeach<T, F, MF>
+    where
+        MF: Fn(Entity) -> C + Send + Sync + 'static,
+        C: Childable<M>,
+    {
+        QueryForeach(self, f)
+    }
+}
+
+impl<T, F, MF, C, M> Childable<(QueryMarker, C, M)> for QueryForeach<T, F, MF>
+where
+    T: SingleObserverTuple,
+    F: WorldQuery + 'static,
+    F::Fetch: FilterFetch,
+    <T::DataQuery as WorldQuery>::Fetch: ReadOnlyFetch,
+    <T::ChangeDetectionQuery as WorldQuery>::Fetch: ReadOnlyFetch,
+    MF: Fn(Entity) -> C + Send + Sync + 'static,
+    C: Childable<M>,
+{
+    fn insert(self, ctx: &mut Ctx) {
+        let parent = ctx.current_entity;
+        let c_parent = ctx
+            .world
+            .spawn()
+            .insert_bundle(ControlBundle::default())
+            .id();
+        ctx.world.entity_mut(parent).push_children(&[c_parent]);
+
+        let f = self.1;
+        let mut children = HashMap::<Entity, Entity>::default();
+        let mut state = TriggerCallState::new(ctx.world);
+        let mut current = vec![];
+        let uf = self.0.register_self(ctx.world, |mut observer, world| {
+            let (uf, marker) = UpdateFunc::new::<QueryForeachUfMarker, _>(world, move |world| {
+                current.clear();
+                current.extend(observer.0.iter(world).map(|(entity, _)| entity));
+                let matching = current.iter().copied().collect::<HashSet<_>>();
+
+                children.retain(|entity, &mut cn| {
+                    if matching.contains(entity) {
+                        return true;
+                    }
+                    let mut params = state.get_mut(world);
+                    if !trigger_transition_out_cn(
+                        cn,
+                        None,
+                        &mut params.0,
+                        &params.1,
+                        &params.2,
+                        &mut params.3,
+                        &mut params.4,
+                    ) {
+                        world.entity_mut(cn).despawn_recursive();
+                    }
+                    false
+                });
+                state.apply(world);
+
+                for &entity in &current {
+                    if children.contains_key(&entity) {
+                        continue;
+                    }
+                    let cn = world.spawn().insert_bundle(ControlBundle::default()).id();
+                    world.entity_mut(c_parent).push_children(&[cn]);
+                    children.insert(entity, cn);
+                    f(entity).insert(&mut Ctx {
+                        world,
+                        current_entity: cn,
+                    });
+                }
+            });
+
+            marker.attach(world, c_parent);
+            uf
+        });
+        uf.run(ctx.world);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use bevy::prelude::*;
+
+    use crate::prelude::*;
+    use crate::testing::UiHarness;
+
+    #[derive(Component)]
+    struct Health(u32);
+    #[derive(Component)]
+    struct Enemy;
+    #[derive(Component)]
+    struct Total(u32);
+
+    #[test]
+    fn fires_when_matches_change() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let runs_c = runs.clone();
+        let mut enemies = vec![];
+        let mut harness = UiHarness::new(Vec2::new(800., 600.), |ctx: Ctx| {
+            enemies.push(ctx.world.spawn().insert_bundle((Health(1), Enemy)).id());
+            enemies.push(ctx.world.spawn().insert_bundle((Health(2), Enemy)).id());
+            ctx.with(query::<(Health,), With<Enemy>>().map(
+                move |enemies: Vec<(Entity, (&Health,))>| {
+                    runs_c.fetch_add(1, Ordering::Relaxed);
+                    Total(enemies.iter().map(|(_, (h,))| h.0).sum())
+                },
+            ))
+        });
+        let root = harness.root().entity();
+        let widget = harness.world().get::<Children>(root).unwrap()[0];
+        let total = |harness: &UiHarness| harness.world().get::<Total>(widget).unwrap().0;
+        assert_eq!(total(&harness), 3);
+        runs.store(0, Ordering::Relaxed);
+
+        harness.world_mut().get_mut::<Health>(enemies[0]).unwrap().0 = 5;
+        harness.update();
+        assert_eq!(runs.load(Ordering::Relaxed), 1);
+        assert_eq!(total(&harness), 7);
+
+        // Entities the filter leaves out don't count
+        harness.world_mut().spawn().insert(Health(10));
+        harness.update();
+        harness.update();
+        assert_eq!(runs.load(Ordering::Relaxed), 1);
+
+        harness.world_mut().despawn(enemies[1]);
+        harness.update();
+        assert_eq!(runs.load(Ordering::Relaxed), 2);
+        assert_eq!(total(&harness), 5);
+    }
+}
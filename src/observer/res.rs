@@ -3,6 +3,7 @@ use std::marker::PhantomData;
 use bevy::ecs::{prelude::*, system::SystemState};
 
 use crate::{
+    debug::{record_dependency, DependencySource},
     lens::Identity,
     prelude::WorldLens,
     runtime::{UiManagedSystems, UiScratchSpace, UpdateFunc},
@@ -70,10 +71,15 @@ impl<R: Send + Sync + 'static> UninitObserver for ResObserverTemplate<R> {
             if let Some(mut list) = world.get_resource_mut::<ResUpdateFuncs<R>>() {
                 list.0.push(uf);
             } else {
-                systems.0.add_system(resource_change_track_system::<R>);
+                systems.add::<ResUpdateFuncs<R>, _>(world, resource_change_track_system::<R>);
                 world.insert_resource(ResUpdateFuncs::<R>(vec![uf], PhantomData));
             };
         });
+        record_dependency(
+            world,
+            DependencySource::Resource(std::any::type_name::<R>()),
+            &ufc,
+        );
         ufc
     }
 }
@@ -84,11 +90,15 @@ pub fn res<R: Send + Sync + 'static>() -> ResLens<R> {
 }
 
 fn resource_change_track_system<T: Send + Sync + 'static>(
+    mut seen: Local<usize>,
     ui: Res<UiScratchSpace>,
     mut update_funcs: ResMut<ResUpdateFuncs<T>>,
     detector: Res<T>,
-) {
+) -> bool {
     if detector.is_changed() {
         ui.process_list(&mut update_funcs.0);
+    } else if ui.any_died_since(&mut seen) {
+        update_funcs.0.retain(|uf| !uf.flagged());
     }
+    !update_funcs.0.is_empty()
 }
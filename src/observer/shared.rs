@@ -0,0 +1,226 @@
+use std::sync::{Arc, Mutex};
+
+use bevy::prelude::World;
+
+use crate::debug::{record_dependency, DependencySource};
+use crate::runtime::{UiScratchSpace, UpdateFunc, WeakUpdateFunc};
+
+use super::{Observer, UninitObserver};
+
+struct SharedValue<T> {
+    value: Arc<T>,
+    // Bumped every time the value changes, so every consumer knows whether it has seen it yet
+    version: usize,
+}
+
+struct SharedState<UO, T> {
+    template: UO,
+    // The update func computing the value, as long as anyone still uses it
+    source: Option<(WeakUpdateFunc, Arc<Mutex<SharedValue<T>>>)>,
+}
+
+pub struct SharedTemplate<UO, T>(Arc<Mutex<SharedState<UO, T>>>);
+
+impl<UO, T> Clone for SharedTemplate<UO, T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<UO, T> SharedTemplate<UO, T> {
+    pub(crate) fn new(template: UO) -> Self {
+        Self(Arc::new(Mutex::new(SharedState {
+            template,
+            source: None,
+        })))
+    }
+}
+
+pub struct SharedObserver<T> {
+    shared: Arc<Mutex<SharedValue<T>>>,
+    current: Arc<T>,
+    version: usize,
+}
+
+struct SharedUfMarker;
+
+impl<UO, O, T> UninitObserver for SharedTemplate<UO, T>
+where
+    UO: UninitObserver<Observer = O> + Clone,
+    O: for<'a> Observer<'a, Return = T>,
+    T: Send + Sync + 'static,
+{
+    type Observer = SharedObserver<T>;
+
+    fn register_self<F: FnOnce(Self::Observer, &mut World) -> UpdateFunc>(
+        self,
+        world: &mut World,
+        uf: F,
+    ) -> UpdateFunc {
+        let mut state = self.0.lock().unwrap();
+        let live_source = state
+            .source
+            .as_ref()
+            .and_then(|(source, shared)| Some((source.upgrade()?, shared.clone())))
+            .filter(|(source, _)| !source.flagged());
+
+        let (source, shared) = match live_source {
+            Some(source) => source,
+            None => {
+                let mut shared = None;
+                let source = state
+                    .template
+                    .clone()
+                    .register_self(world, |mut observer, world| {
+                        let value = Arc::new(Mutex::new(SharedValue {
+                            value: Arc::new(observer.get(world).0),
+                            version: 0,
+                        }));
+                        shared = Some(value.clone());
+
+                        let this = Arc::new(Mutex::new(None::<WeakUpdateFunc>));
+                        let thisc = this.clone();
+                        let source =
+                            UpdateFunc::new_shared::<SharedUfMarker, _>(world, move |world| {
+                                let (val, changed) = observer.get(world);
+                                if !changed {
+                                    return;
+                                }
+                                {
+                                    let mut value = value.lock().unwrap();
+                                    value.value = Arc::new(val);
+                                    value.version += 1;
+                                }
+                                if let Some(this) = thisc
+                                    .lock()
+                                    .unwrap()
+                                    .as_ref()
+                                    .and_then(|this| this.upgrade())
+                                {
+                                    this.trigger_dependents(
+                                        world.get_resource::<UiScratchSpace>().unwrap(),
+                                    );
+                                }
+                            });
+                        *this.lock().unwrap() = Some(source.downgrade());
+                        source
+                    });
+                let shared = shared.unwrap();
+                state.source = Some((source.downgrade(), shared.clone()));
+                (source, shared)
+            }
+        };
+        drop(state);
+
+        let (current, version) = {
+            let value = shared.lock().unwrap();
+            (value.value.clone(), value.version)
+        };
+        let uf = uf(
+            SharedObserver {
+                shared,
+                current,
+                // Makes sure the first get counts as a change
+                version: version.wrapping_sub(1),
+            },
+            world,
+        );
+        source.add_dependent(uf.clone());
+        record_dependency(world, DependencySource::UpdateFunc(source.downgrade()), &uf);
+        uf
+    }
+}
+
+impl<'a, T: Send + Sync + 'static> Observer<'a> for SharedObserver<T> {
+    type Return = &'a T;
+
+    fn get(&'a mut self, _world: &'a World) -> (Self::Return, bool) {
+        let value = self.shared.lock().unwrap();
+        let changed = value.version != self.version;
+        if changed {
+            self.current = value.value.clone();
+            self.version = value.version;
+        }
+        drop(value);
+        (&self.current, changed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use bevy::prelude::*;
+
+    use crate::dom::UiText;
+    use crate::prelude::*;
+    use crate::testing::UiHarness;
+
+    struct Inventory(i32);
+    struct Shown(usize);
+
+    fn texts(harness: &mut UiHarness) -> Vec<String> {
+        let world = harness.world_mut();
+        let mut texts = world
+            .query::<&UiText>()
+            .iter(world)
+            .map(|t| t.0.clone())
+            .collect::<Vec<_>>();
+        texts.sort();
+        texts
+    }
+
+    #[test]
+    fn computes_once_while_anything_uses_it() {
+        let computes = Arc::new(AtomicUsize::new(0));
+        let computes_c = computes.clone();
+        let mut harness = UiHarness::new(Vec2::new(800., 600.), move |ctx: Ctx| {
+            ctx.world.insert_resource(Inventory(1));
+            ctx.world.insert_resource(Shown(2));
+            let total = res::<Inventory>()
+                .map(move |inv: &Inventory| {
+                    computes_c.fetch_add(1, Ordering::Relaxed);
+                    inv.0
+                })
+                .share();
+            ctx.children(
+                res::<Shown>()
+                    .map(|s: &Shown| s.0)
+                    .map_child(move |shown: usize| {
+                        let total = total.clone();
+                        move |ctx: &mut McCtx| {
+                            for _ in 0..shown {
+                                ctx.c(text(total.clone().map(|t: &i32| t.to_string())));
+                            }
+                        }
+                    }),
+            )
+        });
+        assert_eq!(texts(&mut harness), ["1", "1"]);
+        computes.store(0, Ordering::Relaxed);
+
+        harness.world_mut().insert_resource(Inventory(5));
+        harness.update();
+        assert_eq!(computes.load(Ordering::Relaxed), 1);
+        assert_eq!(texts(&mut harness), ["5", "5"]);
+
+        harness.update();
+        assert_eq!(computes.load(Ordering::Relaxed), 1);
+
+        // With nothing using it, the source stops computing
+        harness.world_mut().insert_resource(Shown(0));
+        harness.update();
+        harness.world_mut().insert_resource(Inventory(6));
+        harness.update();
+        assert_eq!(computes.load(Ordering::Relaxed), 1);
+
+        // and comes back once something does again
+        harness.world_mut().insert_resource(Shown(1));
+        harness.update();
+        assert_eq!(texts(&mut harness), ["6"]);
+        harness.world_mut().insert_resource(Inventory(7));
+        harness.update();
+        assert_eq!(texts(&mut harness), ["7"]);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::debug::{record_dependency, DependencySource};
 use crate::runtime::{UiManagedSystems, UiScratchSpace};
 use crate::{observer::UninitObserver, runtime::UpdateFunc};
 use bevy::ecs::prelude::*;
@@ -39,10 +40,15 @@ where
             if let Some(mut list) = world.get_resource_mut::<SingleUpdateFuncs<T>>() {
                 list.0.push(uf.clone());
             } else {
-                systems.0.add_system(single_change_track_system::<T>);
+                systems.add::<SingleUpdateFuncs<T>, _>(world, single_change_track_system::<T>);
                 world.insert_resource(SingleUpdateFuncs::<T>(vec![uf.clone()], PhantomData));
             };
         });
+        record_dependency(
+            world,
+            DependencySource::Single(std::any::type_name::<T>()),
+            &uf,
+        );
         uf
     }
 }
@@ -61,6 +67,88 @@ where
     }
 }
 
+struct OptSingleUpdateFuncs<T: SingleObserverTuple>(Vec<UpdateFunc>, PhantomData<T>);
+
+pub struct UninitOptSingleObserver<T>(PhantomData<T>);
+pub struct OptSingleObserver<T: SingleObserverTuple>(QueryState<T::DataQuery>);
+
+/// Like [`single`], but returns `None` instead of panicking unless exactly one entity matches, so
+/// both nothing matching and several entities matching give `None`. Fires when that changes, as
+/// well as when the components of the matching entity change.
+pub fn opt_single<T: SingleObserverTuple>() -> UninitOptSingleObserver<T> {
+    UninitOptSingleObserver(PhantomData)
+}
+
+impl<T: SingleObserverTuple> UninitObserver for UninitOptSingleObserver<T>
+where
+    <T::DataQuery as WorldQuery>::Fetch: ReadOnlyFetch,
+    <T::ChangeDetectionQuery as WorldQuery>::Fetch: ReadOnlyFetch,
+{
+    type Observer = OptSingleObserver<T>;
+
+    fn register_self<F: FnOnce(Self::Observer, &mut World) -> UpdateFunc>(
+        self,
+        world: &mut World,
+        uf: F,
+    ) -> UpdateFunc {
+        let uf = uf(OptSingleObserver(world.query::<T::DataQuery>()), world);
+        world.resource_scope(|world, mut systems: Mut<UiManagedSystems>| {
+            if let Some(mut list) = world.get_resource_mut::<OptSingleUpdateFuncs<T>>() {
+                list.0.push(uf.clone());
+            } else {
+                systems
+                    .add::<OptSingleUpdateFuncs<T>, _>(world, opt_single_change_track_system::<T>);
+                world.insert_resource(OptSingleUpdateFuncs::<T>(vec![uf.clone()], PhantomData));
+            };
+        });
+        record_dependency(
+            world,
+            DependencySource::Single(std::any::type_name::<T>()),
+            &uf,
+        );
+        uf
+    }
+}
+
+impl<'a, T: SingleObserverTuple> Observer<'a> for OptSingleObserver<T>
+where
+    <T::DataQuery as WorldQuery>::Fetch: ReadOnlyFetch,
+{
+    type Return = Option<<<T::DataQuery as WorldQuery>::ReadOnlyFetch as Fetch<'a, 'a>>::Item>;
+
+    fn get(&'a mut self, world: &'a bevy::prelude::World) -> (Self::Return, bool) {
+        // Matches the change tracking, which only sees an entity while it's the only match
+        let mut iter = self.0.iter(world);
+        let item = iter.next();
+        let item = if iter.next().is_none() { item } else { None };
+        (item, true)
+    }
+}
+
+fn opt_single_change_track_system<T: SingleObserverTuple>(
+    mut seen: Local<usize>,
+    mut last: Local<Option<Entity>>,
+    q: Query<(Entity, T::ChangeDetectionQuery)>,
+    mut list: ResMut<OptSingleUpdateFuncs<T>>,
+    ui: Res<UiScratchSpace>,
+) -> bool
+where
+    <T::ChangeDetectionQuery as WorldQuery>::Fetch: ReadOnlyFetch,
+{
+    // No entities and multiple entities are both treated as none, see `opt_single`
+    let (entity, changed) = match q.get_single() {
+        Ok((entity, flags)) => (Some(entity), T::get_changed(&flags)),
+        Err(_) => (None, false),
+    };
+    if changed || entity != *last {
+        *last = entity;
+        ui.process_list(&mut list.0);
+    } else if ui.any_died_since(&mut seen) {
+        list.0.retain(|uf| !uf.flagged());
+    }
+    !list.0.is_empty()
+}
+
 pub trait SingleObserverTuple: Send + Sync + 'static {
     type DataQuery: WorldQuery;
     type ChangeDetectionQuery: WorldQuery;
@@ -71,16 +159,21 @@ pub trait SingleObserverTuple: Send + Sync + 'static {
 }
 
 fn single_change_track_system<T: SingleObserverTuple>(
+    mut seen: Local<usize>,
     q: Query<T::ChangeDetectionQuery>,
     mut list: ResMut<SingleUpdateFuncs<T>>,
     ui: Res<UiScratchSpace>,
-) where
+) -> bool
+where
     <T::ChangeDetectionQuery as WorldQuery>::Fetch: ReadOnlyFetch,
 {
     let flags = q.single();
     if T::get_changed(&flags) {
         ui.process_list(&mut list.0);
+    } else if ui.any_died_since(&mut seen) {
+        list.0.retain(|uf| !uf.flagged());
     }
+    !list.0.is_empty()
 }
 
 macro_rules! impl_singleobserver_tuple {
@@ -104,3 +197,57 @@ macro_rules! impl_singleobserver_tuple {
 impl_singleobserver_tuple!(A);
 impl_singleobserver_tuple!(A, B);
 impl_singleobserver_tuple!(A, B, C);
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use bevy::prelude::*;
+
+    use crate::prelude::*;
+    use crate::testing::UiHarness;
+
+    #[derive(Component)]
+    struct Player(i32);
+    #[derive(Component)]
+    struct Seen(Option<i32>);
+
+    #[test]
+    fn opt_single_fires_when_the_only_match_changes() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let runs_c = runs.clone();
+        let mut harness = UiHarness::new(Vec2::new(800., 600.), move |ctx: Ctx| {
+            ctx.with(
+                opt_single::<(Player,)>().map(move |player: Option<(&Player,)>| {
+                    runs_c.fetch_add(1, Ordering::Relaxed);
+                    Seen(player.map(|(p,)| p.0))
+                }),
+            )
+        });
+        let root = harness.root().entity();
+        let widget = harness.world().get::<Children>(root).unwrap()[0];
+        let seen = |harness: &UiHarness| harness.world().get::<Seen>(widget).unwrap().0;
+        assert_eq!(seen(&harness), None);
+        runs.store(0, Ordering::Relaxed);
+
+        let player = harness.world_mut().spawn().insert(Player(1)).id();
+        harness.update();
+        assert_eq!(runs.load(Ordering::Relaxed), 1);
+        assert_eq!(seen(&harness), Some(1));
+
+        harness.update();
+        assert_eq!(runs.load(Ordering::Relaxed), 1);
+
+        harness.world_mut().get_mut::<Player>(player).unwrap().0 = 2;
+        harness.update();
+        assert_eq!(runs.load(Ordering::Relaxed), 2);
+        assert_eq!(seen(&harness), Some(2));
+
+        // A second match makes it ambiguous
+        harness.world_mut().spawn().insert(Player(3));
+        harness.update();
+        assert_eq!(runs.load(Ordering::Relaxed), 3);
+        assert_eq!(seen(&harness), None);
+    }
+}
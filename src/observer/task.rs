@@ -0,0 +1,233 @@
+use std::convert::Infallible;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+
+use bevy::ecs::prelude::*;
+use bevy::tasks::{AsyncComputeTaskPool, Task};
+
+use crate::debug::{record_dependency, DependencySource};
+use crate::runtime::{UiScratchSpace, UpdateFunc, WeakUpdateFunc};
+
+use super::{Observer, UninitObserver};
+
+/// The state of a task observed with [`task`] or [`try_task`]. Only the latter can be `Err`.
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub enum TaskStatus<'a, T, E = Infallible> {
+    Pending,
+    Ready(&'a T),
+    Err(&'a E),
+}
+
+impl<T, E> Clone for TaskStatus<'_, T, E> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T, E> Copy for TaskStatus<'_, T, E> {}
+
+type BoxedTask<T, E> = Pin<Box<dyn Future<Output = Result<T, E>> + Send>>;
+
+pub struct UninitTaskObserver<T, E>(Mutex<BoxedTask<T, E>>);
+
+pub struct TaskObserver<T, E> {
+    result: Option<Result<T, E>>,
+    // Filled in by the task once it completes
+    slot: Arc<Mutex<Option<Result<T, E>>>>,
+    first: bool,
+    // Dropping the task cancels it, so it only runs for as long as something observes it
+    _task: Task<()>,
+}
+
+/// Gets an observer for a future, for example a bevy [`Task`], which is run on the
+/// [`AsyncComputeTaskPool`]. It fires once the future completes, so the ui can show a loading
+/// indicator in the meantime. The future is dropped if the widget observing it is despawned
+/// first. Map the status to something owned to build different children for each state with
+/// [`map_child`](crate::childable::ChildMapExt::map_child).
+/// ```
+/// # use bevy::prelude::*;
+/// # use ui4::prelude::*;
+/// # async fn count_saves() -> usize { 0 }
+/// fn save_count(ctx: Ctx) -> Ctx {
+///     ctx.child(text(task(count_saves()).map(
+///         |status: TaskStatus<'_, usize>| match status {
+///             TaskStatus::Ready(n) => format!("{} saves", n),
+///             _ => "Loading...".to_string(),
+///         },
+///     )))
+/// }
+/// ```
+pub fn task<F, T>(future: F) -> UninitTaskObserver<T, Infallible>
+where
+    F: Future<Output = T> + Send + 'static,
+    T: Send + Sync + 'static,
+{
+    UninitTaskObserver(Mutex::new(Box::pin(async move { Ok(future.await) })))
+}
+
+/// Like [`task`], for futures that can fail. An `Err` output is observed as [`TaskStatus::Err`].
+/// ```
+/// # use bevy::prelude::*;
+/// # use ui4::prelude::*;
+/// # async fn list_saves() -> Result<Vec<String>, std::io::Error> { Ok(vec![]) }
+/// fn save_list(ctx: Ctx) -> Ctx {
+///     ctx.child(text(try_task(list_saves()).map(
+///         |status: TaskStatus<'_, Vec<String>, std::io::Error>| match status {
+///             TaskStatus::Pending => "Loading...".to_string(),
+///             TaskStatus::Ready(saves) => format!("{} saves", saves.len()),
+///             TaskStatus::Err(e) => format!("Couldn't list saves: {}", e),
+///         },
+///     )))
+/// }
+/// ```
+pub fn try_task<F, T, E>(future: F) -> UninitTaskObserver<T, E>
+where
+    F: Future<Output = Result<T, E>> + Send + 'static,
+    T: Send + Sync + 'static,
+    E: Send + Sync + 'static,
+{
+    UninitTaskObserver(Mutex::new(Box::pin(future)))
+}
+
+impl<T, E> UninitObserver for UninitTaskObserver<T, E>
+where
+    T: Send + Sync + 'static,
+    E: Send + Sync + 'static,
+{
+    type Observer = TaskObserver<T, E>;
+
+    fn register_self<UF: FnOnce(Self::Observer, &mut World) -> UpdateFunc>(
+        self,
+        world: &mut World,
+        uf: UF,
+    ) -> UpdateFunc {
+        let future = self.0.into_inner().unwrap();
+        let slot = Arc::new(Mutex::new(None));
+        // The task can't hold on to the update func it wakes, as that owns the task
+        let waking = Arc::new(Mutex::new(None::<WeakUpdateFunc>));
+
+        let tx = world
+            .get_resource::<UiScratchSpace>()
+            .unwrap()
+            .hook_sender();
+        let source = DependencySource::Task {
+            id: Arc::as_ptr(&slot) as *const () as usize,
+            output: std::any::type_name::<T>(),
+        };
+        let task_slot = slot.clone();
+        let task_waking = waking.clone();
+        let task = world
+            .get_resource::<AsyncComputeTaskPool>()
+            .expect("observing tasks requires an AsyncComputeTaskPool")
+            .spawn(async move {
+                let result = future.await;
+                *task_slot.lock().unwrap() = Some(result);
+                // Wakes the observer on the next flush. The receiver is only gone if the whole
+                // world has been dropped.
+                let _ = tx.send(Box::new(move |world: &mut World| {
+                    let uf = task_waking
+                        .lock()
+                        .unwrap()
+                        .as_ref()
+                        .and_then(|uf| uf.upgrade());
+                    if let Some(uf) = uf {
+                        world
+                            .get_resource::<UiScratchSpace>()
+                            .unwrap()
+                            .register_update_func(uf);
+                    }
+                }));
+            });
+
+        let uf = uf(
+            TaskObserver {
+                result: None,
+                slot,
+                first: true,
+                _task: task,
+            },
+            world,
+        );
+        *waking.lock().unwrap() = Some(uf.downgrade());
+        record_dependency(world, source, &uf);
+        uf
+    }
+}
+
+impl<'a, T, E> Observer<'a> for TaskObserver<T, E>
+where
+    T: Send + Sync + 'static,
+    E: Send + Sync + 'static,
+{
+    type Return = TaskStatus<'a, T, E>;
+
+    fn get(&'a mut self, _world: &'a World) -> (Self::Return, bool) {
+        let mut changed = std::mem::take(&mut self.first);
+        if self.result.is_none() {
+            if let Some(result) = self.slot.lock().unwrap().take() {
+                self.result = Some(result);
+                changed = true;
+            }
+        }
+        let status = match &self.result {
+            None => TaskStatus::Pending,
+            Some(Ok(val)) => TaskStatus::Ready(val),
+            Some(Err(e)) => TaskStatus::Err(e),
+        };
+        (status, changed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use bevy::prelude::*;
+
+    use crate::prelude::*;
+    use crate::testing::UiHarness;
+
+    fn describe<T: std::fmt::Debug, E: std::fmt::Debug>(status: TaskStatus<'_, T, E>) -> String {
+        match status {
+            TaskStatus::Pending => "pending".to_string(),
+            TaskStatus::Ready(value) => format!("ready {:?}", value),
+            TaskStatus::Err(e) => format!("err {:?}", e),
+        }
+    }
+
+    #[test]
+    fn fires_once_the_task_completes() {
+        let log = Arc::new(Mutex::new(vec![]));
+        let (ok, err, never) = (log.clone(), log.clone(), log.clone());
+        let mut harness = UiHarness::new(Vec2::new(800., 600.), move |ctx: Ctx| {
+            ctx.effect(task(async { 5 }).map(describe), move |s, _| {
+                ok.lock().unwrap().push(s)
+            })
+            .effect(
+                try_task(async { Err::<i32, _>("failed") }).map(describe),
+                move |s, _| err.lock().unwrap().push(s),
+            )
+            .effect(
+                task(std::future::pending::<i32>()).map(describe),
+                move |s, _| never.lock().unwrap().push(s),
+            )
+        });
+
+        for _ in 0..100 {
+            harness.update();
+            if log.lock().unwrap().len() == 2 {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let mut seen = log.lock().unwrap().clone();
+        seen.sort();
+        assert_eq!(seen, ["err \"failed\"", "ready 5"]);
+
+        harness.update();
+        harness.update();
+        assert_eq!(log.lock().unwrap().len(), 2);
+    }
+}
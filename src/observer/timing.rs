@@ -0,0 +1,340 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use bevy::ecs::prelude::*;
+
+use crate::debug::{record_dependency, DependencySource};
+use crate::runtime::{UiClock, UiScratchSpace, UpdateFunc};
+
+use super::{Observer, UninitObserver};
+
+/// Wakes an update func at each of the times in `due`, and keeps going every `period` after that
+/// if it's set. Lives until the update func dies, waiting for more times to be scheduled once
+/// it runs out.
+#[derive(Component)]
+pub(crate) struct TimerWaker {
+    due: VecDeque<f64>,
+    period: Option<f64>,
+    uf: UpdateFunc,
+}
+
+pub(crate) fn waker_system(
+    clock: Res<UiClock>,
+    mut wakers: Query<(Entity, &mut TimerWaker)>,
+    mut commands: Commands,
+    ui: Res<UiScratchSpace>,
+) {
+    let now = clock.elapsed;
+    for (e, mut waker) in wakers.iter_mut() {
+        if waker.uf.flagged() {
+            commands.entity(e).despawn();
+            continue;
+        }
+        let mut woke = None;
+        while waker.due.front().map_or(false, |&due| due <= now) {
+            woke = waker.due.pop_front();
+        }
+        if let Some(mut last) = woke {
+            ui.register_update_func(waker.uf.clone());
+            if let Some(period) = waker.period {
+                while last <= now {
+                    last += period;
+                }
+                waker.due.push_front(last);
+            }
+        }
+    }
+}
+
+fn elapsed(world: &World) -> f64 {
+    world.get_resource::<UiClock>().unwrap().elapsed
+}
+
+#[derive(Clone, Copy)]
+enum Timing {
+    Debounce(f64),
+    Throttle(f64),
+    Delay(f64),
+}
+
+#[derive(Clone)]
+pub struct TimedTemplate<UO>(UO, Timing);
+
+impl<UO> TimedTemplate<UO> {
+    pub(crate) fn debounce(observer: UO, seconds: f32) -> Self {
+        Self(observer, Timing::Debounce(period(seconds)))
+    }
+
+    pub(crate) fn throttle(observer: UO, seconds: f32) -> Self {
+        Self(observer, Timing::Throttle(period(seconds)))
+    }
+
+    pub(crate) fn delay(observer: UO, seconds: f32) -> Self {
+        Self(observer, Timing::Delay(period(seconds)))
+    }
+}
+
+// Timers are only ever woken by the clock moving forward, so a period of 0 would never settle
+fn period(seconds: f32) -> f64 {
+    assert!(
+        seconds > 0.,
+        "timed observers need a positive number of seconds, got {}",
+        seconds
+    );
+    seconds as f64
+}
+
+struct TimedShared<O, T> {
+    observer: O,
+    // The entity with the TimerWaker waking the dependent whenever a pending value is due
+    waker: Entity,
+    // Values waiting to go through, along with when they're due
+    pending: VecDeque<(f64, T)>,
+    // When throttling, the earliest a new value may go through
+    next_allowed: f64,
+}
+
+pub struct Timed<O, T> {
+    current: Option<T>,
+    shared: Arc<Mutex<TimedShared<O, T>>>,
+}
+
+struct TimedUfMarker;
+
+impl<UO, O, T> UninitObserver for TimedTemplate<UO>
+where
+    UO: UninitObserver<Observer = O>,
+    O: for<'a> Observer<'a, Return = T>,
+    T: Send + Sync + 'static,
+{
+    type Observer = Timed<O, T>;
+
+    fn register_self<F: FnOnce(Self::Observer, &mut World) -> UpdateFunc>(
+        self,
+        world: &mut World,
+        uf: F,
+    ) -> UpdateFunc {
+        let timing = self.1;
+        self.0.register_self(world, move |observer, world| {
+            let waker = world.spawn().id();
+            let shared = Arc::new(Mutex::new(TimedShared {
+                observer,
+                waker,
+                pending: VecDeque::new(),
+                next_allowed: elapsed(world),
+            }));
+            let dependent = uf(
+                Timed {
+                    current: None,
+                    shared: shared.clone(),
+                },
+                world,
+            );
+            world.entity_mut(waker).insert(TimerWaker {
+                due: VecDeque::new(),
+                period: None,
+                uf: dependent.clone(),
+            });
+            record_dependency(world, DependencySource::Timer(waker), &dependent);
+            let owner = dependent.clone();
+            UpdateFunc::new_tied::<TimedUfMarker, _>(
+                world,
+                move |world| {
+                    let now = elapsed(world);
+                    let mut shared = shared.lock().unwrap();
+                    let (val, changed) = shared.observer.get(world);
+                    if !changed {
+                        return;
+                    }
+                    // Debouncing replaces whatever the waker was waiting for, while a throttled
+                    // value taking another one's place keeps its wake up
+                    let (due, replace, add) = match timing {
+                        Timing::Debounce(seconds) => {
+                            shared.pending.clear();
+                            (now + seconds, true, true)
+                        }
+                        Timing::Throttle(seconds) => {
+                            // A value still waiting for its turn is simply replaced
+                            if let Some((due, _)) = shared.pending.pop_back() {
+                                (due, false, false)
+                            } else {
+                                let due = shared.next_allowed.max(now);
+                                shared.next_allowed = due + seconds;
+                                (due, false, true)
+                            }
+                        }
+                        Timing::Delay(seconds) => (now + seconds, false, true),
+                    };
+                    shared.pending.push_back((due, val));
+                    let waker = shared.waker;
+                    drop(shared);
+
+                    if due <= now {
+                        world
+                            .get_resource::<UiScratchSpace>()
+                            .unwrap()
+                            .register_update_func(dependent.clone());
+                    } else if add {
+                        if let Some(mut waker) = world.get_mut::<TimerWaker>(waker) {
+                            if replace {
+                                waker.due.clear();
+                            }
+                            waker.due.push_back(due);
+                        }
+                    }
+                },
+                &owner,
+            )
+        })
+    }
+}
+
+impl<'a, O, T> Observer<'a> for Timed<O, T>
+where
+    O: for<'x> Observer<'x, Return = T>,
+    T: Send + Sync + 'static,
+{
+    type Return = &'a T;
+
+    fn get(&'a mut self, world: &'a World) -> (Self::Return, bool) {
+        let now = elapsed(world);
+        let mut shared = self.shared.lock().unwrap();
+        if self.current.is_none() {
+            // The first value goes through right away
+            self.current = Some(shared.observer.get(world).0);
+            return (self.current.as_ref().unwrap(), true);
+        }
+
+        let mut changed = false;
+        while shared.pending.front().map_or(false, |&(due, _)| due <= now) {
+            self.current = shared.pending.pop_front().map(|(_, val)| val);
+            changed = true;
+        }
+        (self.current.as_ref().unwrap(), changed)
+    }
+}
+
+pub struct IntervalObserver {
+    start: f64,
+    period: f64,
+    last: Option<u64>,
+}
+
+/// Gets an observer which fires every `seconds`, returning the number of intervals passed since
+/// it was created. Panics if `seconds` isn't positive.
+/// ```
+/// # use bevy::prelude::*;
+/// # use ui4::prelude::*;
+/// fn blinking_cursor(ctx: Ctx) -> Ctx {
+///     ctx.with(interval(0.5).map(|ticks: u64| {
+///         UiColor(if ticks % 2 == 0 { Color::WHITE } else { Color::NONE })
+///     }))
+/// }
+/// ```
+pub fn interval(seconds: f32) -> IntervalObserver {
+    IntervalObserver {
+        start: 0.,
+        period: period(seconds),
+        last: None,
+    }
+}
+
+impl<'a> Observer<'a> for IntervalObserver {
+    type Return = u64;
+
+    fn get(&'a mut self, world: &'a World) -> (Self::Return, bool) {
+        let ticks = ((elapsed(world) - self.start) / self.period) as u64;
+        let changed = self.last != Some(ticks);
+        self.last = Some(ticks);
+        (ticks, changed)
+    }
+}
+
+impl UninitObserver for IntervalObserver {
+    type Observer = Self;
+
+    fn register_self<F: FnOnce(Self::Observer, &mut World) -> UpdateFunc>(
+        mut self,
+        world: &mut World,
+        uf: F,
+    ) -> UpdateFunc {
+        self.start = elapsed(world);
+        let due = self.start + self.period;
+        let period = self.period;
+        let uf = uf(self, world);
+        let waker = world
+            .spawn()
+            .insert(TimerWaker {
+                due: VecDeque::from([due]),
+                period: Some(period),
+                uf: uf.clone(),
+            })
+            .id();
+        record_dependency(world, DependencySource::Timer(waker), &uf);
+        uf
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+
+    use super::TimerWaker;
+    use crate::prelude::*;
+    use crate::testing::UiHarness;
+
+    struct Label(String);
+
+    #[test]
+    fn debounce_reuses_one_waker() {
+        let mut harness = UiHarness::new(Vec2::new(800., 600.), |ctx: Ctx| {
+            ctx.world.insert_resource(Label("a".to_string()));
+            ctx.child(text(
+                res::<Label>()
+                    .map(|l: &Label| l.0.clone())
+                    .debounce(0.5)
+                    .map(|l: &String| l.clone()),
+            ))
+        });
+        let wakers = harness.find_with::<TimerWaker>().len();
+
+        for label in ["b", "c", "d"] {
+            harness
+                .world_mut()
+                .insert_resource(Label(label.to_string()));
+            harness.update();
+        }
+        assert_eq!(harness.find_with::<TimerWaker>().len(), wakers);
+        assert!(harness.find_by_text("a").is_some());
+
+        harness.advance(0.5);
+        assert!(harness.find_by_text("d").is_some());
+    }
+
+    #[test]
+    #[should_panic]
+    fn zero_intervals_are_rejected() {
+        interval(0.);
+    }
+
+    #[test]
+    fn wakers_go_away_with_their_widget() {
+        let mut harness = UiHarness::new(Vec2::new(800., 600.), |ctx: Ctx| {
+            ctx.world.insert_resource(Label("a".to_string()));
+            ctx.child(text(
+                res::<Label>()
+                    .map(|l: &Label| l.0.clone())
+                    .delay(0.5)
+                    .map(|l: &String| l.clone()),
+            ))
+        });
+        harness.world_mut().insert_resource(Label("b".to_string()));
+        harness.update();
+        assert!(!harness.find_with::<TimerWaker>().is_empty());
+
+        let root = harness.root();
+        root.despawn(harness.world_mut());
+        harness.update();
+        assert!(harness.find_with::<TimerWaker>().is_empty());
+    }
+}
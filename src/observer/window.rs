@@ -0,0 +1,223 @@
+use std::marker::PhantomData;
+
+use bevy::ecs::prelude::*;
+use bevy::math::Vec2;
+use bevy::window::{Window, Windows};
+
+use crate::debug::{record_dependency, DependencySource};
+use crate::runtime::{UiManagedSystems, UiScratchSpace, UpdateFunc};
+
+use super::{Observer, UninitObserver};
+
+/// Something about the primary window which can be observed
+#[doc(hidden)]
+pub trait WindowMetric: Send + Sync + 'static {
+    type Value: PartialEq + Copy + Send + Sync + 'static;
+    const NAME: &'static str;
+
+    fn read(window: Option<&Window>) -> Self::Value;
+}
+
+#[doc(hidden)]
+pub struct WindowSize;
+
+impl WindowMetric for WindowSize {
+    type Value = Vec2;
+    const NAME: &'static str = "size";
+
+    fn read(window: Option<&Window>) -> Vec2 {
+        window.map_or(Vec2::ZERO, |w| Vec2::new(w.width(), w.height()))
+    }
+}
+
+#[doc(hidden)]
+pub struct WindowScaleFactor;
+
+impl WindowMetric for WindowScaleFactor {
+    type Value = f64;
+    const NAME: &'static str = "scale_factor";
+
+    fn read(window: Option<&Window>) -> f64 {
+        window.map_or(1., Window::scale_factor)
+    }
+}
+
+#[doc(hidden)]
+pub struct WindowFocused;
+
+impl WindowMetric for WindowFocused {
+    type Value = bool;
+    const NAME: &'static str = "focused";
+
+    fn read(window: Option<&Window>) -> bool {
+        window.map_or(false, Window::is_focused)
+    }
+}
+
+struct WindowUpdateFuncs<M: WindowMetric> {
+    list: Vec<UpdateFunc>,
+    // The value as of the last time the list was triggered
+    value: M::Value,
+}
+
+pub struct WindowObserver<M: WindowMetric>(Option<M::Value>, PhantomData<M>);
+
+impl<M: WindowMetric> Clone for WindowObserver<M> {
+    fn clone(&self) -> Self {
+        Self(self.0, PhantomData)
+    }
+}
+
+impl<M: WindowMetric> Copy for WindowObserver<M> {}
+
+/// Gets an observer for the logical size of the primary window, which fires when it's resized
+/// ```
+/// # use bevy::prelude::*;
+/// # use ui4::prelude::*;
+/// fn sidebar(ctx: Ctx) -> Ctx {
+///     ctx.children(window_size().map(|size: Vec2| size.x > 800.).map_child(|wide: bool| {
+///         move |ctx: &mut McCtx| {
+///             ctx.c(text(if wide { "Full sidebar" } else { "Compact sidebar" }));
+///         }
+///     }))
+/// }
+/// ```
+pub fn window_size() -> WindowObserver<WindowSize> {
+    WindowObserver(None, PhantomData)
+}
+
+/// Gets an observer for the scale factor of the primary window, which fires when it changes,
+/// for example when the window is moved to another monitor
+pub fn scale_factor() -> WindowObserver<WindowScaleFactor> {
+    WindowObserver(None, PhantomData)
+}
+
+/// Gets an observer for whether the primary window has focus
+pub fn window_focused() -> WindowObserver<WindowFocused> {
+    WindowObserver(None, PhantomData)
+}
+
+fn primary_value<M: WindowMetric>(world: &World) -> M::Value {
+    M::read(
+        world
+            .get_resource::<Windows>()
+            .and_then(Windows::get_primary),
+    )
+}
+
+impl<'a, M: WindowMetric> Observer<'a> for WindowObserver<M> {
+    type Return = M::Value;
+
+    fn get(&'a mut self, world: &'a World) -> (Self::Return, bool) {
+        let value = primary_value::<M>(world);
+        let changed = self.0 != Some(value);
+        self.0 = Some(value);
+        (value, changed)
+    }
+}
+
+impl<M: WindowMetric> UninitObserver for WindowObserver<M> {
+    type Observer = Self;
+
+    fn register_self<F: FnOnce(Self::Observer, &mut World) -> UpdateFunc>(
+        self,
+        world: &mut World,
+        uf: F,
+    ) -> UpdateFunc {
+        let uf = uf(self, world);
+        let ufc = uf.clone();
+        let value = primary_value::<M>(world);
+        world.resource_scope(|world, mut systems: Mut<UiManagedSystems>| {
+            if let Some(mut registry) = world.get_resource_mut::<WindowUpdateFuncs<M>>() {
+                registry.list.push(uf);
+            } else {
+                systems.add::<WindowUpdateFuncs<M>, _>(world, window_track_system::<M>);
+                world.insert_resource(WindowUpdateFuncs::<M> {
+                    list: vec![uf],
+                    value,
+                });
+            };
+        });
+        record_dependency(world, DependencySource::Window(M::NAME), &ufc);
+        ufc
+    }
+}
+
+fn window_track_system<M: WindowMetric>(
+    mut seen: Local<usize>,
+    ui: Res<UiScratchSpace>,
+    mut registry: ResMut<WindowUpdateFuncs<M>>,
+    windows: Option<Res<Windows>>,
+) -> bool {
+    let value = M::read(windows.as_ref().and_then(|w| w.get_primary()));
+    if value != registry.value {
+        registry.value = value;
+        ui.process_list(&mut registry.list);
+    } else if ui.any_died_since(&mut seen) {
+        registry.list.retain(|uf| !uf.flagged());
+    }
+    !registry.list.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use bevy::prelude::*;
+
+    use crate::prelude::*;
+    use crate::testing::UiHarness;
+
+    #[derive(Default)]
+    struct Log {
+        sizes: Vec<Vec2>,
+        scale_factors: Vec<f64>,
+        focus: Vec<bool>,
+    }
+
+    fn primary(harness: &mut UiHarness) -> &mut Window {
+        harness
+            .world_mut()
+            .get_resource_mut::<Windows>()
+            .unwrap()
+            .into_inner()
+            .get_primary_mut()
+            .unwrap()
+    }
+
+    #[test]
+    fn fire_when_the_window_changes() {
+        let log = Arc::new(Mutex::new(Log::default()));
+        let (sizes, scale_factors, focus) = (log.clone(), log.clone(), log.clone());
+        let mut harness = UiHarness::new(Vec2::new(800., 600.), move |ctx: Ctx| {
+            ctx.effect(window_size(), move |size, _| {
+                sizes.lock().unwrap().sizes.push(size)
+            })
+            .effect(scale_factor(), move |factor, _| {
+                scale_factors.lock().unwrap().scale_factors.push(factor)
+            })
+            .effect(window_focused(), move |focused, _| {
+                focus.lock().unwrap().focus.push(focused)
+            })
+        });
+        let focused = primary(&mut harness).is_focused();
+
+        primary(&mut harness).update_actual_size_from_backend(1000, 500);
+        harness.update();
+        primary(&mut harness).update_focused_status_from_backend(!focused);
+        harness.update();
+        harness.update();
+        {
+            let log = log.lock().unwrap();
+            assert_eq!(log.sizes, [Vec2::new(1000., 500.)]);
+            assert!(log.scale_factors.is_empty());
+            assert_eq!(log.focus, [!focused]);
+        }
+
+        primary(&mut harness).update_scale_factor_from_backend(2.);
+        harness.update();
+        let log = log.lock().unwrap();
+        assert_eq!(log.scale_factors, [2.]);
+        assert_eq!(log.sizes, [Vec2::new(1000., 500.), Vec2::new(500., 250.)]);
+    }
+}
@@ -1,24 +1,97 @@
+use std::sync::{Arc, Mutex};
+
 use bevy::app::Plugin;
-use bevy::ecs::prelude::*;
-use bevy::prelude::{Assets, CoreStage};
+use bevy::ecs::{prelude::*, schedule::StateData};
+use bevy::prelude::{Assets, CoreStage, DespawnRecursiveExt};
 use bevy::text::Font;
 use bevy_inspector_egui::RegisterInspectable;
 
+use crate::animation::{trigger_transition_out_cn, TriggerCallState};
 use crate::ctx::Ctx;
 use crate::dom::render::PreExtractedUiNodes;
 use crate::dom::NodeBundle;
-use crate::runtime::{primary_ui_system, UiManagedSystems, UiScratchSpace};
+use crate::runtime::{clock_system, primary_ui_system, UiClock, UiManagedSystems, UiScratchSpace};
 use crate::widgets::{
     button::ButtonSystemState, draggable::DraggableSystemState, textbox::TextBoxSystemState,
 };
 
 #[derive(SystemLabel, Clone, Copy, Hash, PartialEq, Eq, Debug)]
-enum Ui4SystemLabels {
+pub(crate) enum Ui4SystemLabels {
     Layout,
     Shaping,
     Interaction,
 }
 
+/// Configuration for the reactive runtime. Insert it as a resource before adding [`Ui4Plugin`]
+/// to override the defaults.
+pub struct Ui4Settings {
+    /// The maximum number of passes made over triggered update funcs in a single frame.
+    /// Running out means some update funcs kept triggering each other, which gets logged as an
+    /// error along with the culprits. There's always at least one pass, so 0 counts as 1.
+    pub max_flush_passes: usize,
+}
+
+impl Default for Ui4Settings {
+    fn default() -> Self {
+        Self {
+            max_flush_passes: 64,
+        }
+    }
+}
+
+/// Adds the resources and systems making up the reactive runtime, layout included. Shared by
+/// [`Ui4Plugin`] and the test harness, which has no `App` and runs its own stages.
+pub(crate) fn add_runtime(
+    world: &mut World,
+    schedule: &mut Schedule,
+    update: impl StageLabel + Clone,
+    post_update: impl StageLabel + Clone,
+) {
+    fn init<R: FromWorld + Send + Sync + 'static>(world: &mut World) {
+        if !world.contains_resource::<R>() {
+            let resource = R::from_world(world);
+            world.insert_resource(resource);
+        }
+    }
+
+    init::<Ui4Settings>(world);
+    init::<UiScratchSpace>(world);
+    init::<UiClock>(world);
+    init::<ButtonSystemState>(world);
+    init::<TextBoxSystemState>(world);
+    init::<DraggableSystemState>(world);
+    init::<UiManagedSystems>(world);
+
+    schedule
+        .add_system_to_stage(
+            update.clone(),
+            crate::input::interaction_system.label(Ui4SystemLabels::Interaction),
+        )
+        .add_system_to_stage(
+            update.clone(),
+            crate::input::focus_system.after(Ui4SystemLabels::Interaction),
+        )
+        .add_system_to_stage(update.clone(), crate::animation::tween_system)
+        .add_system_to_stage(update.clone(), crate::observer::waker_system)
+        .add_system_to_stage(
+            update.clone(),
+            DraggableSystemState::system.exclusive_system().at_end(),
+        )
+        .add_system_to_stage(update, primary_ui_system.exclusive_system().at_end())
+        .add_system_to_stage(
+            post_update.clone(),
+            crate::animation::transition_system.before(Ui4SystemLabels::Layout),
+        )
+        .add_system_to_stage(
+            post_update.clone(),
+            crate::dom::layout::root_node_system.before(Ui4SystemLabels::Layout),
+        )
+        .add_system_to_stage(
+            post_update,
+            crate::dom::layout::layout_node_system.label(Ui4SystemLabels::Layout),
+        );
+}
+
 pub struct Ui4Plugin;
 impl Plugin for Ui4Plugin {
     fn build(&self, app: &mut bevy::prelude::App) {
@@ -29,11 +102,14 @@ impl Plugin for Ui4Plugin {
             app.init_resource::<bevy_inspector_egui::InspectableRegistry>();
         }
 
-        app.init_resource::<UiScratchSpace>()
-            .init_resource::<ButtonSystemState>()
-            .init_resource::<TextBoxSystemState>()
-            .init_resource::<DraggableSystemState>()
-            .init_resource::<PreExtractedUiNodes>()
+        add_runtime(
+            &mut app.world,
+            &mut app.schedule,
+            CoreStage::Update,
+            CoreStage::PostUpdate,
+        );
+
+        app.init_resource::<PreExtractedUiNodes>()
             .register_inspectable::<crate::dom::Node>()
             .register_inspectable::<crate::dom::ClippedNode>()
             .register_inspectable::<crate::dom::UiText>()
@@ -43,24 +119,8 @@ impl Plugin for Ui4Plugin {
             .register_inspectable::<crate::dom::TextDetails>()
             .register_inspectable::<crate::dom::layout::layout_components::PositionType>()
             .register_inspectable::<crate::dom::layout::layout_components::LayoutType>()
-            .insert_resource(UiManagedSystems(SystemStage::parallel()))
-            .add_system(crate::input::interaction_system.label(Ui4SystemLabels::Interaction))
-            .add_system(crate::input::focus_system.after(Ui4SystemLabels::Interaction))
-            .add_system(crate::animation::tween_system)
-            .add_system(DraggableSystemState::system.exclusive_system().at_end())
-            .add_system(primary_ui_system.exclusive_system().at_end())
-            .add_system_to_stage(
-                CoreStage::PostUpdate,
-                crate::animation::transition_system.before(Ui4SystemLabels::Layout),
-            )
-            .add_system_to_stage(
-                CoreStage::PostUpdate,
-                crate::dom::layout::root_node_system.before(Ui4SystemLabels::Layout),
-            )
-            .add_system_to_stage(
-                CoreStage::PostUpdate,
-                crate::dom::layout::layout_node_system.label(Ui4SystemLabels::Layout),
-            )
+            .add_system_to_stage(CoreStage::First, crate::debug::reset_stats_system)
+            .add_system_to_stage(CoreStage::PreUpdate, clock_system)
             .add_system_to_stage(
                 CoreStage::PostUpdate,
                 crate::dom::render::create_shapes_system
@@ -92,27 +152,219 @@ impl Plugin for Ui4Plugin {
 }
 
 pub struct Ui4Root<F>(pub F);
+
+impl<F> Ui4Root<F> {
+    /// Builds the tree every time `state` is entered, and despawns it when it's exited
+    /// ```no_run
+    /// # use bevy::prelude::*;
+    /// # use ui4::prelude::*;
+    /// #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+    /// enum GameState {
+    ///     InGame,
+    ///     Paused,
+    /// }
+    /// # fn pause_menu(ctx: Ctx) -> Ctx {ctx}
+    /// App::new()
+    ///     .add_plugins(DefaultPlugins)
+    ///     .add_plugin(Ui4Plugin)
+    ///     .add_state(GameState::InGame)
+    ///     .add_plugin(Ui4Root::for_state(GameState::Paused, pause_menu).with_transition_out());
+    /// ```
+    pub fn for_state<S>(state: S, root: F) -> Ui4StateRoot<S, F> {
+        Ui4StateRoot {
+            state,
+            root,
+            transition_out: false,
+        }
+    }
+}
 impl<F: FnOnce(Ctx) -> Ctx + Clone + Send + Sync + 'static> Plugin for Ui4Root<F> {
     fn build(&self, app: &mut bevy::prelude::App) {
         let mut root = Some(self.0.clone());
         app.add_startup_system(
-            (move |world: &mut World| init_ui(world, root.take().unwrap()))
-                .exclusive_system()
-                .at_end(),
+            (move |world: &mut World| {
+                init_ui(world, root.take().unwrap());
+            })
+            .exclusive_system()
+            .at_end(),
+        );
+    }
+}
+
+/// A ui tree that exists while the app is in a particular state, created with
+/// [`Ui4Root::for_state`]
+pub struct Ui4StateRoot<S, F> {
+    state: S,
+    root: F,
+    transition_out: bool,
+}
+
+impl<S, F> Ui4StateRoot<S, F> {
+    /// Plays the out transitions in the tree on exit, and only despawns it once they're done
+    pub fn with_transition_out(mut self) -> Self {
+        self.transition_out = true;
+        self
+    }
+}
+
+impl<S: StateData, F: FnOnce(Ctx) -> Ctx + Clone + Send + Sync + 'static> Plugin
+    for Ui4StateRoot<S, F>
+{
+    fn build(&self, app: &mut bevy::prelude::App) {
+        let handle = Arc::new(Mutex::new(None::<UiRootHandle>));
+
+        let root = self.root.clone();
+        let enter_handle = handle.clone();
+        app.add_system_set(
+            SystemSet::on_enter(self.state.clone()).with_system(
+                (move |world: &mut World| {
+                    *enter_handle.lock().unwrap() = Some(init_ui(world, root.clone()));
+                })
+                .exclusive_system(),
+            ),
+        );
+
+        let transition_out = self.transition_out;
+        app.add_system_set(
+            SystemSet::on_exit(self.state.clone()).with_system(
+                (move |world: &mut World| {
+                    if let Some(handle) = handle.lock().unwrap().take() {
+                        if transition_out {
+                            handle.transition_out(world);
+                        } else {
+                            handle.despawn(world);
+                        }
+                    }
+                })
+                .exclusive_system(),
+            ),
         );
     }
 }
 
 /// Initialize a new ui tree
-pub fn init_ui(world: &mut World, root: impl FnOnce(Ctx) -> Ctx) {
+pub fn init_ui(world: &mut World, root: impl FnOnce(Ctx) -> Ctx) -> UiRootHandle {
+    let entity = world
+        .spawn()
+        .insert_bundle(NodeBundle::default())
+        .insert(crate::prelude::Width(crate::prelude::Units::Auto))
+        .insert(crate::prelude::Height(crate::prelude::Units::Auto))
+        .id();
     (Ctx {
-        current_entity: world
-            .spawn()
-            .insert_bundle(NodeBundle::default())
-            .insert(crate::prelude::Width(crate::prelude::Units::Auto))
-            .insert(crate::prelude::Height(crate::prelude::Units::Auto))
-            .id(),
+        current_entity: entity,
         world,
     })
     .child(root);
+    UiRootHandle(entity)
+}
+
+/// A ui tree created with [`init_ui`]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct UiRootHandle(Entity);
+
+impl UiRootHandle {
+    /// The root node of the tree
+    pub fn entity(&self) -> Entity {
+        self.0
+    }
+
+    /// Removes the whole tree, along with everything the runtime kept around for it.
+    ///
+    /// Called from outside of ui4, the unmount hooks have run, and the update funcs, registry
+    /// entries and change detection systems that were only there for this tree are gone by the time
+    /// this returns. Inside ui4, for example in an [`OnClick`](crate::prelude::OnClick) handler,
+    /// that happens at the end of the update already underway instead. Either way, tweens and
+    /// timers still running for the tree are despawned the next time they would have ticked.
+    pub fn despawn(self, world: &mut World) {
+        crate::batch(world, |world| {
+            if let Some(entity) = world.get_entity_mut(self.0) {
+                entity.despawn_recursive();
+            }
+        });
+    }
+
+    /// Plays the out transitions in the tree, and despawns it once they're done. Despawns it
+    /// right away if there's nothing to play.
+    pub fn transition_out(self, world: &mut World) {
+        if world.get_entity(self.0).is_none() {
+            return;
+        }
+        let mut state = TriggerCallState::new(world);
+        let mut params = state.get_mut(world);
+        let blocked = trigger_transition_out_cn(
+            self.0,
+            None,
+            &mut params.0,
+            &params.1,
+            &params.2,
+            &mut params.3,
+            &mut params.4,
+        );
+        state.apply(world);
+        if !blocked {
+            self.despawn(world);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+
+    use super::add_runtime;
+    use crate::prelude::*;
+    use crate::testing::insert_headless_resources;
+
+    #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+    enum Menu {
+        Closed,
+        Open,
+    }
+
+    #[derive(Component)]
+    struct MenuRoot;
+
+    fn menus(app: &mut App) -> Vec<Entity> {
+        app.world
+            .query_filtered::<Entity, With<MenuRoot>>()
+            .iter(&app.world)
+            .collect()
+    }
+
+    #[test]
+    fn state_roots_follow_their_state() {
+        let mut app = App::new();
+        insert_headless_resources(&mut app.world, Vec2::new(800., 600.));
+        add_runtime(
+            &mut app.world,
+            &mut app.schedule,
+            CoreStage::Update,
+            CoreStage::PostUpdate,
+        );
+        app.add_state(Menu::Closed)
+            .add_plugin(Ui4Root::for_state(Menu::Open, |ctx: Ctx| {
+                ctx.with(MenuRoot)
+            }));
+        app.update();
+        assert!(menus(&mut app).is_empty());
+
+        let set_state = |app: &mut App, menu| {
+            app.world
+                .get_resource_mut::<State<Menu>>()
+                .unwrap()
+                .set(menu)
+                .unwrap();
+            app.update();
+        };
+        set_state(&mut app, Menu::Open);
+        let open = menus(&mut app);
+        assert_eq!(open.len(), 1);
+
+        // Staying in the state keeps the same tree
+        app.update();
+        assert_eq!(menus(&mut app), open);
+
+        set_state(&mut app, Menu::Closed);
+        assert!(menus(&mut app).is_empty());
+    }
 }
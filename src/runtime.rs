@@ -1,24 +1,89 @@
 use std::{
+    any::TypeId,
+    collections::BTreeSet,
+    fmt::Debug,
     hash::Hash,
     marker::PhantomData,
-    ptr::addr_of_mut,
-    sync::{atomic::AtomicBool, Arc, Mutex},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize},
+        Arc, Mutex, Weak,
+    },
 };
 
-use bevy::ecs::prelude::*;
+use bevy::{
+    core::Time,
+    ecs::{prelude::*, system::BoxedSystem},
+    utils::{HashMap, Instant},
+};
+use crossbeam_channel::{Receiver, Sender};
 use dashmap::DashSet;
 
-use crate::widgets::{button::ButtonSystemState, textbox::TextBoxSystemState};
+use crate::{
+    debug::{record_dependency, DependencySource, StatsCollector, Ui4Stats},
+    plugin::Ui4Settings,
+    widgets::{button::ButtonSystemState, textbox::TextBoxSystemState},
+};
+
+/// A callback run by the runtime outside of any update func, such as mount and unmount hooks
+pub(crate) type Hook = Box<dyn FnOnce(&mut World) + Send + Sync>;
+
+struct HookQueue(Sender<Hook>, Receiver<Hook>);
+
+impl Default for HookQueue {
+    fn default() -> Self {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        Self(tx, rx)
+    }
+}
 
 #[derive(Default)]
 pub(crate) struct UiScratchSpace {
-    update_hashset_a: DashSet<UpdateFunc, ahash::RandomState>,
-    update_hashset_b: DashSet<UpdateFunc, ahash::RandomState>,
+    pending: DashSet<UpdateFunc, ahash::RandomState>,
+    // Non-zero while inside a batch or a flush, in which case nothing else should flush
+    batch_depth: Arc<AtomicUsize>,
+    hooks: HookQueue,
+    // Bumped every time an update func dies, so registries know when they have something to
+    // prune. Shared with every update func made for this world.
+    deaths: Arc<AtomicUsize>,
 }
 
 impl UiScratchSpace {
     pub fn register_update_func(&self, uf: UpdateFunc) {
-        self.update_hashset_a.insert(uf);
+        self.pending.insert(uf);
+    }
+
+    /// Queues a hook to be run on the next flush
+    pub fn queue_hook(&self, hook: Hook) {
+        self.hooks.0.send(hook).unwrap();
+    }
+
+    /// A sender for queueing hooks from places without access to the world, like `Drop` impls
+    pub fn hook_sender(&self) -> Sender<Hook> {
+        self.hooks.0.clone()
+    }
+
+    /// Moves the pending update funcs which are deeper than `depth` into the queue. Anything else
+    /// stays pending, and gets picked up by the next pass.
+    fn schedule_pending(&self, depth: Option<usize>, queue: &mut BTreeSet<(usize, UpdateFunc)>) {
+        self.pending.retain(|uf| {
+            let uf_depth = uf.depth();
+            if depth.map_or(true, |depth| uf_depth > depth) {
+                queue.insert((uf_depth, uf.clone()));
+                false
+            } else {
+                true
+            }
+        });
+    }
+
+    /// Whether any update func died since the last time `seen` was passed in. Registries of update
+    /// funcs keep a `Local` for this, and drop their flagged funcs when it returns true, instead of
+    /// waiting for whatever they're tracking to change.
+    pub fn any_died_since(&self, seen: &mut usize) -> bool {
+        let generation = self.deaths.load(std::sync::atomic::Ordering::Relaxed);
+        let died = generation != *seen;
+        *seen = generation;
+        died
     }
 
     pub fn process_list(&self, list: &mut Vec<UpdateFunc>) {
@@ -32,34 +97,219 @@ impl UiScratchSpace {
     }
 }
 
-// Contains internal change detection systems
-pub(crate) struct UiManagedSystems(pub(crate) SystemStage);
+/// The time as seen by tweens, transitions, timed observers and key repeat. It follows bevy's
+/// `Time` in apps, and is stepped by hand in the test harness so tests don't depend on the wall
+/// clock.
+#[derive(Default)]
+pub(crate) struct UiClock {
+    /// Seconds since startup
+    pub elapsed: f64,
+    /// Seconds since the previous frame
+    pub delta: f32,
+}
+
+impl UiClock {
+    pub fn advance(&mut self, seconds: f32) {
+        self.elapsed += seconds as f64;
+        self.delta = seconds;
+    }
+}
+
+pub(crate) fn clock_system(time: Res<Time>, mut clock: ResMut<UiClock>) {
+    clock.elapsed = time.seconds_since_startup();
+    clock.delta = time.delta_seconds();
+}
+
+// Contains internal change detection systems, keyed by the registry of update funcs they work on.
+// Each system returns whether its registry still has anything in it, once it doesn't, both the
+// system and the registry are removed.
+#[derive(Default)]
+pub(crate) struct UiManagedSystems(HashMap<TypeId, (BoxedSystem<(), bool>, fn(&mut World))>);
+
+impl UiManagedSystems {
+    /// Adds the change detection system for the registry resource `R`
+    pub fn add<R: Send + Sync + 'static, P>(
+        &mut self,
+        world: &mut World,
+        system: impl IntoSystem<(), bool, P>,
+    ) {
+        let mut system: BoxedSystem<(), bool> = Box::new(system.system());
+        system.initialize(world);
+        self.0.insert(
+            TypeId::of::<R>(),
+            (system, remove_registry::<R> as fn(&mut World)),
+        );
+    }
+
+    fn run(&mut self, world: &mut World) {
+        self.0.retain(|_, (system, remove)| {
+            let keep = system.run((), world);
+            system.apply_buffers(world);
+            system.check_change_tick(world.change_tick());
+            if !keep {
+                remove(world);
+            }
+            keep
+        });
+    }
+}
+
+fn remove_registry<R: Send + Sync + 'static>(world: &mut World) {
+    world.remove_resource::<R>();
+}
 
 pub(crate) fn primary_ui_system(world: &mut World) {
-    world.resource_scope(|world, mut buttons: Mut<ButtonSystemState>| {
-        buttons.run(world);
-    });
-    world.resource_scope(|world, mut textbox: Mut<TextBoxSystemState>| {
-        textbox.run(world);
+    batch(world, |world| {
+        world.resource_scope(|world, mut buttons: Mut<ButtonSystemState>| {
+            buttons.run(world);
+        });
+        world.resource_scope(|world, mut textbox: Mut<TextBoxSystemState>| {
+            textbox.run(world);
+        });
     });
+}
+
+/// Runs `f`, then flushes the reactive updates caused by its changes to the world right away,
+/// instead of leaving them for ui4's own update later in the frame. This is for code outside of
+/// ui4, like systems or commands, which needs the ui to be up to date before the frame goes on.
+///
+/// Batches nest, and only the outermost one flushes. Inside ui4 (for example in an
+/// [`OnClick`](crate::prelude::OnClick) handler) an update is already underway and picks up the
+/// changes on its own, so `batch` just runs `f`. Without [`Ui4Plugin`](crate::plugin::Ui4Plugin)
+/// there's nothing to flush, and it does the same.
+/// ```
+/// # use bevy::prelude::*;
+/// # struct Health(u32);
+/// # struct Mana(u32);
+/// fn respawn(world: &mut World) {
+///     ui4::batch(world, |world| {
+///         world.get_resource_mut::<Health>().unwrap().0 = 100;
+///         world.get_resource_mut::<Mana>().unwrap().0 = 50;
+///     });
+/// }
+/// ```
+pub fn batch<R>(world: &mut World, f: impl FnOnce(&mut World) -> R) -> R {
+    let depth = match world.get_resource::<UiScratchSpace>() {
+        Some(ui) => ui.batch_depth.clone(),
+        None => return f(world),
+    };
+    let guard = BatchGuard::enter(depth.clone());
+    let r = f(world);
+    drop(guard);
+    if depth.load(std::sync::atomic::Ordering::Relaxed) == 0 {
+        flush(world);
+    }
+    r
+}
+
+// Holds the batch depth up while alive, so it comes back down even if whatever runs inside panics
+struct BatchGuard(Arc<AtomicUsize>);
+
+impl BatchGuard {
+    fn enter(depth: Arc<AtomicUsize>) -> Self {
+        depth.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        Self(depth)
+    }
+}
+
+impl Drop for BatchGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
+    }
+}
+
+fn flush(world: &mut World) {
+    let _guard = BatchGuard::enter(
+        world
+            .get_resource::<UiScratchSpace>()
+            .unwrap()
+            .batch_depth
+            .clone(),
+    );
+    run_hooks(world);
     world.resource_scope(|world, mut systems: Mut<UiManagedSystems>| {
-        systems.0.run(world);
+        systems.run(world);
     });
-    loop {
-        let ui = &mut *world.get_resource_mut::<UiScratchSpace>().unwrap();
-        std::mem::swap(&mut ui.update_hashset_a, &mut ui.update_hashset_b);
-        let update_hashset = std::mem::take(&mut ui.update_hashset_b);
+    run_update_funcs(world);
+    // Update funcs building new widgets queue their mount hooks, run them in the same frame
+    run_hooks(world);
+}
 
-        for uf in update_hashset.iter() {
-            uf.run(world);
-        }
-        update_hashset.clear();
+fn run_hooks(world: &mut World) {
+    let ui = world.get_resource::<UiScratchSpace>().unwrap();
+    let hooks = ui.hooks.1.try_iter().collect::<Vec<_>>();
+    for hook in hooks {
+        hook(world);
+    }
+}
 
-        let ui = &mut *world.get_resource_mut::<UiScratchSpace>().unwrap();
-        ui.update_hashset_b = update_hashset;
-        if ui.update_hashset_a.is_empty() {
+fn run_update_funcs(world: &mut World) {
+    // Update funcs are run in order of their depth in the dependency graph, so by the time one runs
+    // everything it depends on has already settled. Anything triggered at or below the depth
+    // currently being processed (which only happens with cycles) is left for the next pass.
+    let max_passes = world
+        .get_resource::<Ui4Settings>()
+        .unwrap()
+        .max_flush_passes
+        .max(1);
+    let mut stats = world
+        .contains_resource::<Ui4Stats>()
+        .then(StatsCollector::default);
+    let mut queue = BTreeSet::new();
+    let mut passes = 0;
+    while passes < max_passes {
+        let ui = world.get_resource::<UiScratchSpace>().unwrap();
+        ui.schedule_pending(None, &mut queue);
+        if queue.is_empty() {
             break;
         }
+        passes += 1;
+
+        while let Some(next) = queue.iter().next().cloned() {
+            queue.remove(&next);
+            let (depth, uf) = next;
+            // Switching sources (as flatten does) can push an update func deeper after it was
+            // queued, in which case it has to wait for its new sources
+            if uf.depth() > depth {
+                queue.insert((uf.depth(), uf));
+                continue;
+            }
+            if let Some(stats) = &mut stats {
+                if uf.flagged() {
+                    stats.skipped += 1;
+                } else {
+                    let start = Instant::now();
+                    uf.run(world);
+                    stats.record(&uf, start.elapsed());
+                }
+            } else {
+                uf.run(world);
+            }
+
+            let ui = world.get_resource::<UiScratchSpace>().unwrap();
+            ui.schedule_pending(Some(depth), &mut queue);
+        }
+    }
+
+    // Whatever is still pending at this point is part of a cycle. Drop it, so the next frame
+    // doesn't pick the cycle back up.
+    let ui = world.get_resource::<UiScratchSpace>().unwrap();
+    let stuck = ui.pending.iter().map(|uf| uf.clone()).collect::<Vec<_>>();
+    if !stuck.is_empty() {
+        ui.pending.clear();
+        bevy::log::error!(
+            "ui4 gave up on updating after {} passes, as these update funcs kept triggering each other: {}",
+            max_passes,
+            stuck
+                .iter()
+                .map(|uf| format!("{:?}", uf))
+                .collect::<Vec<_>>()
+                .join(", "),
+        );
+    }
+
+    if let Some(stats) = stats {
+        stats.finish(world, passes, &stuck);
     }
 }
 
@@ -67,6 +317,24 @@ pub(crate) fn primary_ui_system(world: &mut World) {
 pub struct UpdateFunc(Arc<UfInner<dyn FnMut(&mut World) + Send + Sync>>);
 struct UfInner<F: ?Sized> {
     flag: AtomicBool,
+    // The length of the longest chain of update funcs leading to this one. Update funcs triggered
+    // by registries rather than by other update funcs start out at 0.
+    depth: AtomicUsize,
+    name: &'static str,
+    entity: Mutex<Option<Entity>>,
+    // For update funcs made with `new_shared`, the update funcs they trigger
+    dependents: Option<Mutex<Vec<UpdateFunc>>>,
+    // For update funcs without a marker of their own, how many of the update funcs they exist for
+    // are still alive. The last of those to die flags this one.
+    live_dependents: AtomicUsize,
+    // The update funcs without a marker which exist for this one, and die once it and their other
+    // dependents have
+    keeps_alive: Mutex<Vec<WeakUpdateFunc>>,
+    // Every update func this one feeds, whichever list they're kept in, so depth increases can be
+    // passed down
+    feeds: Mutex<Vec<WeakUpdateFunc>>,
+    // The death counter of the world this update func belongs to
+    deaths: Arc<AtomicUsize>,
     func: Mutex<F>,
 }
 
@@ -79,38 +347,55 @@ pub(crate) struct UfMarker<T> {
 
 impl<T> Drop for UfMarker<T> {
     fn drop(&mut self) {
-        self.arc
-            .flag
-            .store(true, std::sync::atomic::Ordering::Relaxed);
+        self.update_func().die();
     }
 }
 
 impl<T> UfMarker<T> {
     pub fn add_dependent(&mut self, uf: UpdateFunc) {
+        self.update_func().link(&uf);
         self.list.push(uf);
     }
 
-    pub fn trigger(&mut self, ctx: &mut UiScratchSpace) {
-        ctx.process_list(&mut self.list);
+    /// Records `entity` as the owner of the update func, for diagnostics.
+    pub fn set_owner(&self, entity: Entity) {
+        *self.arc.entity.lock().unwrap() = Some(entity);
     }
 
-    pub fn forget(self) {
-        use std::mem::{ManuallyDrop, MaybeUninit};
+    /// Inserts the marker on `entity`, and records it as the owner of the update func.
+    pub fn attach(self, world: &mut World, entity: Entity)
+    where
+        Self: Component,
+    {
+        self.set_owner(entity);
+        world.entity_mut(entity).insert(self);
+    }
 
-        let mut md = ManuallyDrop::new(MaybeUninit::new(self));
-        unsafe {
-            std::ptr::read(addr_of_mut!((*md.as_mut_ptr()).arc));
-            std::ptr::read(addr_of_mut!((*md.as_mut_ptr()).list));
-        }
+    /// The update func this marker belongs to
+    pub fn update_func(&self) -> UpdateFunc {
+        UpdateFunc(self.arc.clone())
+    }
+
+    pub fn trigger(&mut self, ctx: &mut UiScratchSpace) {
+        ctx.process_list(&mut self.list);
     }
 }
 
 impl UpdateFunc {
     pub(crate) fn new<T, F: FnMut(&mut World) + Send + Sync + 'static>(
+        world: &World,
         func: F,
     ) -> (Self, UfMarker<T>) {
         let arc = Arc::new(UfInner {
             flag: AtomicBool::new(false),
+            depth: AtomicUsize::new(0),
+            name: std::any::type_name::<T>(),
+            entity: Mutex::new(None),
+            dependents: None,
+            live_dependents: AtomicUsize::new(0),
+            keeps_alive: Mutex::new(vec![]),
+            feeds: Mutex::new(vec![]),
+            deaths: deaths(world),
             func: Mutex::new(func),
         });
         (
@@ -122,6 +407,130 @@ impl UpdateFunc {
             },
         )
     }
+
+    /// Creates an update func without a marker, which dies along with `owner`. For helpers that
+    /// only exist to feed `owner`, like tweens.
+    pub(crate) fn new_tied<T, F: FnMut(&mut World) + Send + Sync + 'static>(
+        world: &mut World,
+        func: F,
+        owner: &UpdateFunc,
+    ) -> Self {
+        let uf = Self(Arc::new(UfInner {
+            flag: AtomicBool::new(false),
+            depth: AtomicUsize::new(0),
+            name: std::any::type_name::<T>(),
+            entity: Mutex::new(owner.entity()),
+            dependents: None,
+            live_dependents: AtomicUsize::new(1),
+            keeps_alive: Mutex::new(vec![]),
+            feeds: Mutex::new(vec![]),
+            deaths: owner.0.deaths.clone(),
+            func: Mutex::new(func),
+        }));
+        owner.0.keeps_alive.lock().unwrap().push(uf.downgrade());
+        uf.link(owner);
+        record_dependency(world, DependencySource::UpdateFunc(uf.downgrade()), owner);
+        uf
+    }
+
+    /// Creates an update func without a marker, which feeds any number of other update funcs
+    /// added with [`add_dependent`](Self::add_dependent), and dies once all of them have.
+    pub(crate) fn new_shared<T, F: FnMut(&mut World) + Send + Sync + 'static>(
+        world: &World,
+        func: F,
+    ) -> Self {
+        Self(Arc::new(UfInner {
+            flag: AtomicBool::new(false),
+            depth: AtomicUsize::new(0),
+            name: std::any::type_name::<T>(),
+            entity: Mutex::new(None),
+            dependents: Some(Mutex::new(vec![])),
+            live_dependents: AtomicUsize::new(0),
+            keeps_alive: Mutex::new(vec![]),
+            feeds: Mutex::new(vec![]),
+            deaths: deaths(world),
+            func: Mutex::new(func),
+        }))
+    }
+
+    /// Adds a dependent to an update func created with [`new_shared`](Self::new_shared)
+    pub(crate) fn add_dependent(&self, uf: UpdateFunc) {
+        if uf.flagged() {
+            return;
+        }
+        self.link(&uf);
+        self.0
+            .live_dependents
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        uf.0.keeps_alive.lock().unwrap().push(self.downgrade());
+        let mut dependents = self.0.dependents.as_ref().unwrap().lock().unwrap();
+        dependents.retain(|uf| !uf.flagged());
+        dependents.push(uf);
+    }
+
+    /// Triggers the dependents of an update func created with [`new_shared`](Self::new_shared)
+    pub(crate) fn trigger_dependents(&self, ui: &UiScratchSpace) {
+        let mut dependents = self.0.dependents.as_ref().unwrap().lock().unwrap();
+        ui.process_list(&mut dependents);
+    }
+
+    // Flags this update func, along with the update funcs it was the last one keeping alive
+    fn die(&self) {
+        if self.0.flag.swap(true, std::sync::atomic::Ordering::Relaxed) {
+            return;
+        }
+        self.0
+            .deaths
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let kept = std::mem::take(&mut *self.0.keeps_alive.lock().unwrap());
+        for uf in kept.iter().filter_map(WeakUpdateFunc::upgrade) {
+            let live =
+                uf.0.live_dependents
+                    .fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
+            if live == 1 {
+                uf.die();
+            }
+        }
+    }
+
+    /// Records that this update func feeds `dependent`, which has to run after it
+    fn link(&self, dependent: &UpdateFunc) {
+        let mut feeds = self.0.feeds.lock().unwrap();
+        feeds.retain(|uf| uf.0.strong_count() > 0);
+        feeds.push(dependent.downgrade());
+        drop(feeds);
+        dependent.raise_depth(self.depth() + 1, &mut vec![]);
+    }
+
+    // Makes this update func at least `depth` deep, and pushes everything it feeds down along with
+    // it. `path` holds the update funcs being raised further up the chain, so cycles end.
+    fn raise_depth(&self, depth: usize, path: &mut Vec<usize>) {
+        let old = self
+            .0
+            .depth
+            .fetch_max(depth, std::sync::atomic::Ordering::Relaxed);
+        if old >= depth || path.contains(&self.id()) {
+            return;
+        }
+        path.push(self.id());
+        let feeds = self
+            .0
+            .feeds
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(WeakUpdateFunc::upgrade)
+            .collect::<Vec<_>>();
+        for uf in feeds {
+            uf.raise_depth(depth + 1, path);
+        }
+        path.pop();
+    }
+
+    pub(crate) fn downgrade(&self) -> WeakUpdateFunc {
+        WeakUpdateFunc(Arc::downgrade(&self.0))
+    }
+
     pub fn run(&self, world: &mut World) {
         if !self.flagged() {
             (self.0.func.lock().unwrap())(world);
@@ -131,6 +540,57 @@ impl UpdateFunc {
     pub fn flagged(&self) -> bool {
         self.0.flag.load(std::sync::atomic::Ordering::Relaxed)
     }
+
+    pub fn depth(&self) -> usize {
+        self.0.depth.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// A number uniquely identifying this update func while it's alive
+    pub fn id(&self) -> usize {
+        Arc::as_ptr(&self.0) as *const () as usize
+    }
+
+    /// The type name of the marker this update func was created with
+    pub fn name(&self) -> &'static str {
+        self.0.name
+    }
+
+    /// The entity the marker of this update func was attached to, if any
+    pub fn entity(&self) -> Option<Entity> {
+        *self.0.entity.lock().unwrap()
+    }
+}
+
+fn deaths(world: &World) -> Arc<AtomicUsize> {
+    world
+        .get_resource::<UiScratchSpace>()
+        .unwrap()
+        .deaths
+        .clone()
+}
+
+/// An [`UpdateFunc`] which doesn't keep it alive
+#[derive(Clone)]
+pub(crate) struct WeakUpdateFunc(Weak<UfInner<dyn FnMut(&mut World) + Send + Sync>>);
+
+impl WeakUpdateFunc {
+    pub fn upgrade(&self) -> Option<UpdateFunc> {
+        self.0.upgrade().map(UpdateFunc)
+    }
+
+    /// The [`id`](UpdateFunc::id) of the update func this points to
+    pub fn id(&self) -> usize {
+        self.0.as_ptr() as *const () as usize
+    }
+}
+
+impl Debug for UpdateFunc {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.entity() {
+            Some(entity) => write!(f, "{} on {:?}", self.name(), entity),
+            None => write!(f, "{}", self.name()),
+        }
+    }
 }
 
 impl Eq for UpdateFunc {}
@@ -154,3 +614,285 @@ impl Ord for UpdateFunc {
         Arc::as_ptr(&self.0).cmp(&Arc::as_ptr(&other.0))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+
+    use bevy::prelude::*;
+
+    use super::*;
+    use crate::animation::ActiveTween;
+    use crate::dom::UiText;
+    use crate::lens::ComponentLens;
+    use crate::observer::UninitObserver;
+    use crate::prelude::*;
+    use crate::testing::UiHarness;
+
+    #[derive(Component)]
+    struct Val(i32);
+    #[derive(Component)]
+    struct Out(i32);
+    #[derive(Component)]
+    struct Sum(i32);
+    struct Source(i32);
+    struct Which(bool);
+
+    // `first` is fed by `Source`, `third` two steps after it. `Out` flattens to either of them, and
+    // `Sum` reads both `Out` and `first`, which makes a diamond whose depth changes with `Which`.
+    fn diamond(runs: Arc<AtomicUsize>) -> impl FnOnce(Ctx) -> Ctx {
+        move |ctx: Ctx| {
+            ctx.world.insert_resource(Source(0));
+            ctx.world.insert_resource(Which(true));
+
+            let mut first = None;
+            let mut second = None;
+            let mut third = None;
+            let ctx = ctx
+                .child(|ctx: Ctx| {
+                    first = Some(ctx.current_entity());
+                    ctx.with(res::<Source>().map(|s: &Source| Val(s.0)))
+                })
+                .child(|ctx: Ctx| {
+                    second = Some(ctx.current_entity());
+                    ctx.with(component::<Val>(first.unwrap()).map(|v: &Val| Val(v.0 + 1)))
+                })
+                .child(|ctx: Ctx| {
+                    third = Some(ctx.current_entity());
+                    ctx.with(component::<Val>(second.unwrap()).map(|v: &Val| Val(v.0 + 1)))
+                });
+            let (first, third) = (first.unwrap(), third.unwrap());
+
+            let out = ctx.component::<Out>();
+            ctx.with(
+                res::<Which>()
+                    .map(move |w: &Which| component::<Val>(if w.0 { first } else { third }))
+                    .flatten()
+                    .map(
+                        |v: FlattenReturn<'_, <ComponentLens<Val> as UninitObserver>::Observer>| {
+                            Out(v.0)
+                        },
+                    ),
+            )
+            .with(
+                combine((out, component::<Val>(first))).map(move |(out, v): (&Out, &Val)| {
+                    runs.fetch_add(1, Ordering::Relaxed);
+                    Sum(out.0 + v.0)
+                }),
+            )
+        }
+    }
+
+    #[test]
+    fn update_funcs_run_once_per_change_after_flatten_switch() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let mut harness = UiHarness::new(Vec2::new(800., 600.), diamond(runs.clone()));
+        let root = harness.root().entity();
+
+        harness.world_mut().insert_resource(Source(1));
+        runs.store(0, Ordering::Relaxed);
+        harness.update();
+        assert_eq!(runs.load(Ordering::Relaxed), 1);
+        assert_eq!(harness.world().get::<Sum>(root).unwrap().0, 2);
+
+        harness.world_mut().insert_resource(Which(false));
+        runs.store(0, Ordering::Relaxed);
+        harness.update();
+        assert_eq!(runs.load(Ordering::Relaxed), 1);
+        assert_eq!(harness.world().get::<Sum>(root).unwrap().0, 4);
+
+        // `Out` now sits below `third`, so `Sum` has to wait for it instead of also running as soon
+        // as `first` changes
+        harness.world_mut().insert_resource(Source(5));
+        runs.store(0, Ordering::Relaxed);
+        harness.update();
+        assert_eq!(runs.load(Ordering::Relaxed), 1);
+        assert_eq!(harness.world().get::<Sum>(root).unwrap().0, 12);
+    }
+
+    struct Looping;
+
+    // An update func which triggers itself every time it runs
+    fn looping(world: &mut World, runs: Arc<AtomicUsize>) -> UfMarker<Looping> {
+        let this = Arc::new(Mutex::new(None::<WeakUpdateFunc>));
+        let this_c = this.clone();
+        let (uf, marker) = UpdateFunc::new::<Looping, _>(world, move |world| {
+            runs.fetch_add(1, Ordering::Relaxed);
+            let this = this_c.lock().unwrap().as_ref().unwrap().upgrade().unwrap();
+            world
+                .get_resource::<UiScratchSpace>()
+                .unwrap()
+                .register_update_func(this);
+        });
+        *this.lock().unwrap() = Some(uf.downgrade());
+        world
+            .get_resource::<UiScratchSpace>()
+            .unwrap()
+            .register_update_func(uf);
+        marker
+    }
+
+    fn cycle_harness(max_flush_passes: usize) -> (UiHarness, Arc<AtomicUsize>, UfMarker<Looping>) {
+        let mut harness = UiHarness::new(Vec2::new(800., 600.), |ctx| ctx);
+        harness
+            .world_mut()
+            .insert_resource(Ui4Settings { max_flush_passes });
+        harness.world_mut().insert_resource(Ui4Stats::default());
+        let runs = Arc::new(AtomicUsize::new(0));
+        let marker = looping(harness.world_mut(), runs.clone());
+        (harness, runs, marker)
+    }
+
+    #[test]
+    fn cycles_stop_at_the_pass_cap() {
+        let (mut harness, runs, _marker) = cycle_harness(5);
+        harness.update();
+        assert_eq!(runs.load(Ordering::Relaxed), 5);
+        let stats = harness.world().get_resource::<Ui4Stats>().unwrap();
+        assert_eq!(stats.flush_passes, 5);
+        assert_eq!(stats.stuck.len(), 1);
+        assert_eq!(stats.stuck[0].0, std::any::type_name::<Looping>());
+
+        // What was left of the cycle got dropped, so the next frame doesn't pick it back up
+        harness.update();
+        assert_eq!(runs.load(Ordering::Relaxed), 5);
+        assert!(harness
+            .world()
+            .get_resource::<Ui4Stats>()
+            .unwrap()
+            .stuck
+            .is_empty());
+    }
+
+    #[test]
+    fn zero_flush_passes_still_makes_one_pass() {
+        let (mut harness, runs, _marker) = cycle_harness(0);
+        harness.update();
+        assert_eq!(runs.load(Ordering::Relaxed), 1);
+        let stats = harness.world().get_resource::<Ui4Stats>().unwrap();
+        assert_eq!(stats.stuck.len(), 1);
+    }
+
+    struct Show(bool);
+    struct Label(String);
+
+    fn managed_systems(harness: &UiHarness) -> usize {
+        harness
+            .world()
+            .get_resource::<UiManagedSystems>()
+            .unwrap()
+            .0
+            .len()
+    }
+
+    #[test]
+    fn registries_are_removed_once_empty() {
+        let mut harness = UiHarness::new(Vec2::new(800., 600.), |ctx: Ctx| {
+            ctx.world.insert_resource(Show(true));
+            ctx.world.insert_resource(Label("label".to_string()));
+            ctx.children(res::<Show>().map(|s: &Show| s.0).map_child(|show: bool| {
+                move |ctx: &mut McCtx| {
+                    if show {
+                        ctx.c(text(res::<Label>().map(|l: &Label| l.0.clone())));
+                    }
+                }
+            }))
+        });
+        assert!(harness.find_by_text("label").is_some());
+        let before = managed_systems(&harness);
+
+        harness.world_mut().insert_resource(Show(false));
+        harness.update();
+        assert!(harness.find_by_text("label").is_none());
+        // The registry for `Label` notices its last update func died on the next flush
+        harness.update();
+        assert_eq!(managed_systems(&harness), before - 1);
+    }
+
+    #[test]
+    fn despawning_a_root_leaves_nothing_behind() {
+        let mut harness = UiHarness::new(Vec2::new(800., 600.), |ctx: Ctx| {
+            ctx.world.insert_resource(Source(0));
+            ctx.world.insert_resource(Label("a".to_string()));
+            ctx.with(
+                res::<Source>()
+                    .map(|s: &Source| s.0 as f32)
+                    .tween(1.)
+                    .map(|v: f32| Val(v as i32)),
+            )
+            .child(text(res::<Label>().map(|l: &Label| l.0.clone())))
+        });
+        harness.world_mut().insert_resource(Source(10));
+        harness.update();
+        assert_eq!(harness.find_with::<ActiveTween>().len(), 1);
+        assert!(managed_systems(&harness) > 0);
+
+        let root = harness.root();
+        root.despawn(harness.world_mut());
+        assert_eq!(managed_systems(&harness), 0);
+        assert!(harness.world().get_entity(root.entity()).is_none());
+
+        // The tween notices its widget is gone the next time it ticks
+        harness.update();
+        assert!(harness.find_with::<ActiveTween>().is_empty());
+    }
+
+    #[test]
+    fn batch_flushes_right_away() {
+        let mut harness = UiHarness::new(Vec2::new(800., 600.), |ctx: Ctx| {
+            ctx.world.insert_resource(Label("a".to_string()));
+            ctx.child(text(res::<Label>().map(|l: &Label| l.0.clone())))
+        });
+
+        crate::batch(harness.world_mut(), |world| {
+            world.insert_resource(Label("b".to_string()));
+        });
+        assert!(harness.find_by_text("b").is_some());
+
+        // A panic inside a batch doesn't leave later ones thinking they're nested
+        let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            crate::batch(harness.world_mut(), |_| panic!("failed inside a batch"))
+        }));
+        assert!(panicked.is_err());
+        crate::batch(harness.world_mut(), |world| {
+            world.insert_resource(Label("c".to_string()));
+        });
+        assert!(harness.find_by_text("c").is_some());
+    }
+
+    #[test]
+    fn stats_add_up_every_flush_of_a_frame() {
+        let mut harness = UiHarness::new(Vec2::new(800., 600.), |ctx: Ctx| {
+            ctx.world.insert_resource(Label("a".to_string()));
+            ctx.child(text(res::<Label>().map(|l: &Label| l.0.clone())))
+        });
+        harness.world_mut().insert_resource(Ui4Stats::default());
+
+        for label in ["b", "c"] {
+            crate::batch(harness.world_mut(), |world| {
+                world.insert_resource(Label(label.to_string()));
+            });
+        }
+        let stats = harness.world().get_resource::<Ui4Stats>().unwrap();
+        assert_eq!(stats.flush_passes, 2);
+        let text_runs = stats
+            .funcs
+            .iter()
+            .filter(|f| f.name == std::any::type_name::<UiText>())
+            .map(|f| f.runs)
+            .sum::<usize>();
+        assert_eq!(text_runs, 2);
+
+        // The next frame starts over
+        harness.update();
+        let stats = harness.world().get_resource::<Ui4Stats>().unwrap();
+        assert_eq!(stats.flush_passes, 0);
+        assert_eq!(stats.runs, 0);
+    }
+
+    #[test]
+    fn batch_without_the_plugin_just_runs() {
+        let mut world = World::new();
+        assert_eq!(crate::batch(&mut world, |_| 5), 5);
+    }
+}
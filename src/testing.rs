@@ -0,0 +1,375 @@
+use std::fmt::Write;
+
+use bevy::{
+    ecs::prelude::*,
+    input::{keyboard::KeyCode, mouse::MouseButton, touch::Touches, Input},
+    math::Vec2,
+    prelude::Children,
+    sprite::Rect,
+    tasks::{AsyncComputeTaskPool, TaskPool},
+    ui::UiColor,
+    window::{ReceivedCharacter, Window, WindowDescriptor, WindowId, Windows},
+};
+
+use crate::{
+    ctx::Ctx,
+    debug::Ui4Stats,
+    dom::{layout::layout_components::*, ClippedNode, Control, Node, UiText},
+    plugin::{add_runtime, init_ui, Ui4SystemLabels, UiRootHandle},
+    runtime::UiClock,
+};
+
+#[cfg(test)]
+mod tests;
+
+/// How far the clock moves each frame of a [`UiHarness`], in seconds
+pub const FRAME_TIME: f32 = 1. / 60.;
+
+#[derive(StageLabel, Clone, Copy, Hash, PartialEq, Eq, Debug)]
+enum HarnessStage {
+    Update,
+    PostUpdate,
+}
+
+/// Runs a ui tree without a window or a GPU, for testing widgets.
+///
+/// The harness runs everything ui4 does except for rendering, including layout, and lets you
+/// inject input. Each input method simulates a frame, so its effects are visible right after
+/// it returns. Positions are in ui coordinates, with the origin at the top left.
+/// ```
+/// # use bevy::prelude::*;
+/// # use ui4::{prelude::*, testing::UiHarness};
+/// let mut harness = UiHarness::new(Vec2::new(800., 600.), |ctx| ctx.child(button("Hello")));
+/// let button = harness.find_by_text("Hello").unwrap();
+/// harness.click_entity(button);
+/// ```
+pub struct UiHarness {
+    world: World,
+    schedule: Schedule,
+    root: UiRootHandle,
+}
+
+impl UiHarness {
+    /// Builds `root` into a window of the given size, and runs the first frame.
+    pub fn new(window_size: Vec2, root: impl FnOnce(Ctx) -> Ctx) -> Self {
+        let mut world = World::new();
+        insert_headless_resources(&mut world, window_size);
+
+        let mut schedule = Schedule::default();
+        schedule
+            .add_stage(HarnessStage::Update, SystemStage::single_threaded())
+            .add_stage(HarnessStage::PostUpdate, SystemStage::single_threaded());
+        add_runtime(
+            &mut world,
+            &mut schedule,
+            HarnessStage::Update,
+            HarnessStage::PostUpdate,
+        );
+        // Stands in for shaping, which needs the render app
+        schedule.add_system_to_stage(
+            HarnessStage::PostUpdate,
+            crate::dom::render::clip_system.after(Ui4SystemLabels::Layout),
+        );
+
+        let root = init_ui(&mut world, root);
+
+        let mut harness = Self {
+            world,
+            schedule,
+            root,
+        };
+        harness.update();
+        harness
+    }
+
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+
+    /// Runs a single frame, which moves the clock forward by [`FRAME_TIME`].
+    pub fn update(&mut self) {
+        if let Some(mut stats) = self.world.get_resource_mut::<Ui4Stats>() {
+            *stats = Ui4Stats::default();
+        }
+        self.world
+            .get_resource_mut::<UiClock>()
+            .unwrap()
+            .advance(FRAME_TIME);
+
+        self.schedule.run(&mut self.world);
+
+        self.world
+            .get_resource_mut::<Input<MouseButton>>()
+            .unwrap()
+            .clear();
+        self.world
+            .get_resource_mut::<Input<KeyCode>>()
+            .unwrap()
+            .clear();
+        self.world
+            .get_resource_mut::<Events<ReceivedCharacter>>()
+            .unwrap()
+            .update();
+    }
+
+    /// Runs as many frames as it takes for `seconds` to pass, for letting tweens, transitions
+    /// and timed observers play out. The clock is simulated, so this returns right away.
+    pub fn advance(&mut self, seconds: f32) {
+        let frames = (seconds / FRAME_TIME).ceil() as usize;
+        for _ in 0..frames {
+            self.update();
+        }
+    }
+
+    /// Moves the cursor to `pos`.
+    pub fn move_cursor(&mut self, pos: Vec2) {
+        let mut windows = self.world.get_resource_mut::<Windows>().unwrap();
+        let window = windows.get_primary_mut().unwrap();
+        let height = window.height();
+        window.update_cursor_position_from_backend(Some(Vec2::new(pos.x, height - pos.y)));
+        self.update();
+    }
+
+    /// Presses the left mouse button wherever the cursor is.
+    pub fn press(&mut self) {
+        self.world
+            .get_resource_mut::<Input<MouseButton>>()
+            .unwrap()
+            .press(MouseButton::Left);
+        self.update();
+    }
+
+    /// Releases the left mouse button wherever the cursor is.
+    pub fn release(&mut self) {
+        self.world
+            .get_resource_mut::<Input<MouseButton>>()
+            .unwrap()
+            .release(MouseButton::Left);
+        self.update();
+    }
+
+    /// Moves the cursor to `pos`, and clicks there.
+    pub fn click(&mut self, pos: Vec2) {
+        self.move_cursor(pos);
+        self.press();
+        self.release();
+    }
+
+    /// Clicks at the center of a node.
+    pub fn click_entity(&mut self, entity: Entity) {
+        let rect = self.rect(entity);
+        self.click((rect.min + rect.max) / 2.);
+    }
+
+    /// Types `text` into whatever is focused.
+    pub fn type_text(&mut self, text: &str) {
+        let mut events = self
+            .world
+            .get_resource_mut::<Events<ReceivedCharacter>>()
+            .unwrap();
+        for char in text.chars() {
+            events.send(ReceivedCharacter {
+                id: WindowId::primary(),
+                char,
+            });
+        }
+        self.update();
+    }
+
+    /// Presses and releases a key.
+    pub fn press_key(&mut self, key: KeyCode) {
+        self.world
+            .get_resource_mut::<Input<KeyCode>>()
+            .unwrap()
+            .press(key);
+        self.update();
+        self.world
+            .get_resource_mut::<Input<KeyCode>>()
+            .unwrap()
+            .release(key);
+        self.update();
+    }
+
+    /// The root entity of the tree being tested.
+    pub fn root(&self) -> UiRootHandle {
+        self.root
+    }
+
+    /// Finds the first node displaying exactly `text`.
+    pub fn find_by_text(&mut self, text: &str) -> Option<Entity> {
+        self.world
+            .query::<(Entity, &UiText)>()
+            .iter(&self.world)
+            .find(|(_, t)| t.0 == text)
+            .map(|(e, _)| e)
+    }
+
+    /// Finds every node with a `T` component, usually a marker.
+    pub fn find_with<T: Component>(&mut self) -> Vec<Entity> {
+        self.world
+            .query_filtered::<Entity, With<T>>()
+            .iter(&self.world)
+            .collect()
+    }
+
+    /// Serializes the whole tree, see [`snapshot`].
+    pub fn snapshot(&self) -> String {
+        snapshot(&self.world, self.root.entity())
+    }
+
+    /// The text displayed by a node, if any.
+    pub fn text(&self, entity: Entity) -> Option<&str> {
+        self.world.get::<UiText>(entity).map(|t| &*t.0)
+    }
+
+    /// The laid out rect of a node.
+    pub fn rect(&self, entity: Entity) -> Rect {
+        let node = self.world.get::<Node>(entity).unwrap();
+        Rect {
+            min: node.pos,
+            max: node.pos + node.size,
+        }
+    }
+
+    /// Asserts that a node was laid out at `pos` with the given `size`.
+    #[track_caller]
+    pub fn assert_rect(&self, entity: Entity, pos: Vec2, size: Vec2) {
+        let node = self.world.get::<Node>(entity).unwrap();
+        assert!(
+            node.pos.abs_diff_eq(pos, 0.01) && node.size.abs_diff_eq(size, 0.01),
+            "expected {:?} to be at {} with size {}, but it's at {} with size {}",
+            entity,
+            pos,
+            size,
+            node.pos,
+            node.size,
+        );
+    }
+}
+
+/// Inserts the resources a window and input plugins would, for running the ui without them
+pub(crate) fn insert_headless_resources(world: &mut World, window_size: Vec2) {
+    let mut windows = Windows::default();
+    windows.add(Window::new(
+        WindowId::primary(),
+        &WindowDescriptor {
+            width: window_size.x,
+            height: window_size.y,
+            ..Default::default()
+        },
+        window_size.x as u32,
+        window_size.y as u32,
+        1.,
+        None,
+    ));
+    world.insert_resource(windows);
+    world.insert_resource(Input::<MouseButton>::default());
+    world.insert_resource(Input::<KeyCode>::default());
+    world.insert_resource(Touches::default());
+    world.insert_resource(Events::<ReceivedCharacter>::default());
+    world.insert_resource(AsyncComputeTaskPool(TaskPool::new()));
+}
+
+/// Serializes the tree under `root` into stable, human readable text, for golden-file tests.
+///
+/// Every node gets a line with its computed position, size, clip rect and z layer, followed by
+/// the layout components, text and color it has. Children are indented below their parent.
+/// Control nodes are left out, with their children listed in their place. Positions are rounded
+/// to two decimals, so the output only changes when the layout actually does.
+pub fn snapshot(world: &World, root: Entity) -> String {
+    let mut out = String::new();
+    snapshot_node(world, root, 0, &mut out);
+    out
+}
+
+fn snapshot_node(world: &World, entity: Entity, mut depth: usize, out: &mut String) {
+    if world.get::<Control>(entity).is_none() {
+        let indent = "  ".repeat(depth);
+        write!(out, "{}node", indent).unwrap();
+        if let Some(node) = world.get::<Node>(entity) {
+            write!(
+                out,
+                " pos=({:.2}, {:.2}) size=({:.2}, {:.2})",
+                node.pos.x, node.pos.y, node.size.x, node.size.y
+            )
+            .unwrap();
+        }
+        if let Some(clip) = world.get::<ClippedNode>(entity) {
+            write!(
+                out,
+                " clip=({:.2}, {:.2})..({:.2}, {:.2}) z={}",
+                clip.min.x, clip.min.y, clip.max.x, clip.max.y, clip.z_layer
+            )
+            .unwrap();
+        }
+        out.push('\n');
+
+        macro_rules! components {
+            ($($name:ident),* $(,)?) => {
+                $(
+                    if let Some(c) = world.get::<$name>(entity) {
+                        writeln!(out, "{}  {:?}", indent, c).unwrap();
+                    }
+                )*
+            };
+        }
+        components![
+            Width,
+            Height,
+            Left,
+            Right,
+            Top,
+            Bottom,
+            MinLeft,
+            MaxLeft,
+            MinRight,
+            MaxRight,
+            MinTop,
+            MaxTop,
+            MinBottom,
+            MaxBottom,
+            MinWidth,
+            MaxWidth,
+            MinHeight,
+            MaxHeight,
+            ChildLeft,
+            ChildRight,
+            ChildTop,
+            ChildBottom,
+            RowBetween,
+            ColBetween,
+            RowIndex,
+            ColIndex,
+            RowSpan,
+            ColSpan,
+            Border,
+            PositionType,
+            LayoutType,
+            GridRows,
+            GridCols,
+        ];
+        if let Some(text) = world.get::<UiText>(entity) {
+            writeln!(out, "{}  UiText({:?})", indent, text.0).unwrap();
+        }
+        if let Some(color) = world.get::<UiColor>(entity) {
+            let [r, g, b, a] = color.0.as_rgba_f32();
+            writeln!(
+                out,
+                "{}  UiColor({:.3}, {:.3}, {:.3}, {:.3})",
+                indent, r, g, b, a
+            )
+            .unwrap();
+        }
+        depth += 1;
+    }
+
+    if let Some(children) = world.get::<Children>(entity) {
+        for &child in children.iter() {
+            snapshot_node(world, child, depth, out);
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use bevy::prelude::*;
+
+use super::UiHarness;
+use crate::prelude::*;
+
+#[derive(Component)]
+struct Count(i32);
+
+fn counter(ctx: Ctx) -> Ctx {
+    let this = ctx.current_entity();
+    let count = ctx.component();
+    ctx.with(Count(0))
+        .child(button("+").with(OnClick::new(move |world| {
+            world.get_mut::<Count>(this).unwrap().0 += 1;
+        })))
+        .child(text(count.map(|c: &Count| c.0.to_string())).with(Height(Units::Pixels(30.))))
+}
+
+#[test]
+fn clicking_a_button_updates_text() {
+    let mut harness = UiHarness::new(Vec2::new(800., 600.), counter);
+    assert!(harness.find_by_text("0").is_some());
+
+    let button = harness.find_by_text("+").unwrap();
+    harness.click_entity(button);
+    assert!(harness.find_by_text("0").is_none());
+    assert!(harness.find_by_text("1").is_some());
+
+    harness.click_entity(button);
+    assert!(harness.find_by_text("2").is_some());
+}
+
+#[test]
+fn snapshot_of_a_fixed_tree() {
+    let harness = UiHarness::new(Vec2::new(800., 600.), |ctx: Ctx| {
+        ctx.with(Width(Units::Pixels(200.)))
+            .with(Height(Units::Pixels(100.)))
+            .with(Left(Units::Pixels(10.)))
+            .with(Top(Units::Pixels(20.)))
+            .child(
+                text("hi")
+                    .with(Width(Units::Pixels(50.)))
+                    .with(Height(Units::Pixels(30.)))
+                    .with(Left(Units::Pixels(5.)))
+                    .with(Top(Units::Pixels(5.))),
+            )
+    });
+    assert_eq!(
+        harness.snapshot(),
+        "\
+node pos=(0.00, 0.00) size=(800.00, 600.00) clip=(0.00, 0.00)..(800.00, 600.00) z=0
+  Width(Pixels(800.0))
+  Height(Pixels(600.0))
+  node pos=(10.00, 20.00) size=(200.00, 100.00) clip=(10.00, 20.00)..(210.00, 120.00) z=1
+    Width(Pixels(200.0))
+    Height(Pixels(100.0))
+    Left(Pixels(10.0))
+    Top(Pixels(20.0))
+    node pos=(15.00, 25.00) size=(50.00, 30.00) clip=(15.00, 25.00)..(65.00, 55.00) z=2
+      Width(Pixels(50.0))
+      Height(Pixels(30.0))
+      Left(Pixels(5.0))
+      Top(Pixels(5.0))
+      UiText(\"hi\")
+"
+    );
+}
@@ -54,21 +54,15 @@ pub fn button<O: IntoObserver<String, M>, M: 'static>(t: O) -> impl FnOnce(Ctx)
 
         let text_size = ctx.opt_component().map(|x: Option<&TextSize>| x.copied());
 
-        let color = hover.and(normal).and(click).map(|((h, n), c)| (h, n, c));
-
         ctx.with(Interaction::None)
             .with(Height(Units::Pixels(30.)))
-            .with(
-                component
-                    .and(color)
-                    .map(
-                        |(interaction, (h, n, c)): (&Interaction, _)| match interaction {
-                            Interaction::Clicked => UiColor(c),
-                            Interaction::Hovered => UiColor(h),
-                            Interaction::None => UiColor(n),
-                        },
-                    ),
-            )
+            .with(combine((component, hover, normal, click)).map(
+                |(interaction, h, n, c): (&Interaction, _, _, _)| match interaction {
+                    Interaction::Clicked => UiColor(c),
+                    Interaction::Hovered => UiColor(h),
+                    Interaction::None => UiColor(n),
+                },
+            ))
             .with(FuncScratch::default())
             .child(
                 text(t)
@@ -269,7 +263,12 @@ pub fn slider(percent: impl WorldLens<Out = f32>) -> impl FnOnce(Ctx) -> Ctx {
 
 pub fn vscroll_view<M>(inner: impl Childable<M>) -> impl FnOnce(Ctx) -> Ctx {
     |ctx: Ctx| {
-        let avail_height = ctx.component().map(|node: &Node| node.size.y);
+        // The layout also changes with the clip rect, which scrolling moves every frame
+        let avail_height = ctx
+            .layout()
+            .map(|layout: NodeLayout| layout.size.y)
+            .dedup()
+            .copied();
         let mut content_height = None;
         let mut container_entity = None;
         let ctx = ctx.with(LayoutType::Row).child(|ctx| {
@@ -278,7 +277,11 @@ pub fn vscroll_view<M>(inner: impl Childable<M>) -> impl FnOnce(Ctx) -> Ctx {
                 .with(MinHeight(Units::Pixels(0.)))
                 .with(Height(Units::Percentage(100.)))
                 .child(|ctx| {
-                    let ch = ctx.component().map(|node: &Node| node.size.y);
+                    let ch = ctx
+                        .layout()
+                        .map(|layout: NodeLayout| layout.size.y)
+                        .dedup()
+                        .copied();
                     content_height = Some(ch);
                     let heights_obs = ch.and(avail_height);
                     container_entity = Some(ctx.current_entity());
@@ -4,6 +4,7 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use super::Focused;
+use crate::runtime::UiClock;
 
 #[derive(Component)]
 pub struct TextBox(pub usize);
@@ -25,7 +26,7 @@ pub(crate) struct TextBoxSystemState {
         EventReader<'static, 'static, ReceivedCharacter>,
         Query<'static, 'static, (&'static TextBoxFunc, &'static mut TextBox), With<Focused>>,
         Res<'static, Input<KeyCode>>,
-        Res<'static, Time>,
+        Res<'static, UiClock>,
     )>,
     tmp_chars: Vec<char>,
     timer: Timer,
@@ -43,7 +44,7 @@ impl FromWorld for TextBoxSystemState {
 
 impl TextBoxSystemState {
     pub(crate) fn run(&mut self, world: &mut World) {
-        let (mut reader, mut q, inp, time) = self.state.get_mut(world);
+        let (mut reader, mut q, inp, clock) = self.state.get_mut(world);
         self.tmp_chars.extend(reader.iter().map(|rc| rc.char));
         if let Ok((tbf, cursor)) = q.get_single_mut() {
             let pl = inp.pressed(KeyCode::Left);
@@ -51,7 +52,7 @@ impl TextBoxSystemState {
             let jpl = inp.just_pressed(KeyCode::Left);
             let jpr = inp.just_pressed(KeyCode::Right);
             let mut cursor = cursor.0;
-            let delta = time.delta();
+            let delta = Duration::from_secs_f32(clock.delta);
             let string = tbf.clone().get(world);
             if cursor > string.len() {
                 cursor = 0;